@@ -1,202 +1,384 @@
-use std::collections::{HashSet, VecDeque};
-
+use std::{
+    collections::HashSet,
+    env, fs,
+    io::{self, Write},
+    str::FromStr,
+};
+
+use aoc::Direction;
+use itertools::Itertools;
 use pathfinding::matrix::Matrix;
 
 const INPUT: &str = include_str!("../input/day10.txt");
 
-struct Grid(Matrix<char>);
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Pipe {
+    Vertical,
+    Horizontal,
+    NorthEast,
+    NorthWest,
+    SouthWest,
+    SouthEast,
+    Ground,
+    Start,
+}
+
+const CONNECTIONS: [(Pipe, [Direction; 2]); 6] = [
+    (Pipe::Vertical, [Direction::North, Direction::South]),
+    (Pipe::Horizontal, [Direction::East, Direction::West]),
+    (Pipe::NorthEast, [Direction::North, Direction::East]),
+    (Pipe::NorthWest, [Direction::North, Direction::West]),
+    (Pipe::SouthWest, [Direction::South, Direction::West]),
+    (Pipe::SouthEast, [Direction::South, Direction::East]),
+];
+
+impl TryFrom<char> for Pipe {
+    type Error = char;
+
+    fn try_from(c: char) -> Result<Self, Self::Error> {
+        Ok(match c {
+            '|' => Pipe::Vertical,
+            '-' => Pipe::Horizontal,
+            'L' => Pipe::NorthEast,
+            'J' => Pipe::NorthWest,
+            '7' => Pipe::SouthWest,
+            'F' => Pipe::SouthEast,
+            '.' => Pipe::Ground,
+            'S' => Pipe::Start,
+            c => return Err(c),
+        })
+    }
+}
+
+impl From<Pipe> for char {
+    fn from(pipe: Pipe) -> Self {
+        match pipe {
+            Pipe::Vertical => '|',
+            Pipe::Horizontal => '-',
+            Pipe::NorthEast => 'L',
+            Pipe::NorthWest => 'J',
+            Pipe::SouthWest => '7',
+            Pipe::SouthEast => 'F',
+            Pipe::Ground => '.',
+            Pipe::Start => 'S',
+        }
+    }
+}
+
+impl Pipe {
+    fn connections(self) -> Option<[Direction; 2]> {
+        CONNECTIONS
+            .iter()
+            .find(|&&(pipe, _)| pipe == self)
+            .map(|&(_, directions)| directions)
+    }
+
+    fn connects(self, direction: Direction) -> bool {
+        self.connections()
+            .is_some_and(|directions| directions.contains(&direction))
+    }
+
+    fn from_connections(a: Direction, b: Direction) -> Option<Self> {
+        CONNECTIONS
+            .iter()
+            .find(|(_, directions)| directions.contains(&a) && directions.contains(&b) && a != b)
+            .map(|&(pipe, _)| pipe)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Tile {
+    Loop,
+    Inside,
+    Outside,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Format {
+    Text,
+    Svg,
+    Ppm,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "svg" => Ok(Format::Svg),
+            "ppm" => Ok(Format::Ppm),
+            _ => Err(format!(
+                "unknown render format {s:?}, expected text, svg or ppm"
+            )),
+        }
+    }
+}
+
+struct Grid {
+    pipes: Matrix<Pipe>,
+    start_pipe: Pipe,
+    /// Loop tiles in walking order, starting at `S`.
+    path: Vec<(usize, usize)>,
+}
+
+impl FromStr for Grid {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rows = s
+            .lines()
+            .enumerate()
+            .map(|(row, line)| {
+                line.chars()
+                    .enumerate()
+                    .map(|(col, c)| {
+                        Pipe::try_from(c)
+                            .map_err(|c| format!("unknown tile {c:?} at ({row}, {col})"))
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let pipes = Matrix::from_rows(rows).map_err(|err| err.to_string())?;
+        let (start_pipe, path) = Self::trace_loop(&pipes)?;
+
+        Ok(Self {
+            pipes,
+            start_pipe,
+            path,
+        })
+    }
+}
 
 impl Grid {
     fn new(input: impl AsRef<str>) -> Self {
-        Self(Matrix::from_iter(
-            input.as_ref().lines().map(|line| line.chars()),
-        ))
+        input.as_ref().parse().unwrap()
     }
 
-    fn find_path(&self) -> (char, Vec<(usize, usize)>) {
-        let mut queue = VecDeque::new();
-        let mut visited = HashSet::new();
-        queue.push_back(self.find_start());
-        visited.insert(self.find_start());
-
-        let mut potential_s = HashSet::from(['|', 'J', 'L', '7', 'F', '-']);
-
-        while let Some(pos) = queue.pop_front() {
-            let (row, col) = pos;
-            let ch = *self.0.get(pos).unwrap();
-
-            if row > 0
-                && matches!(ch, 'S' | '|' | 'J' | 'L')
-                && matches!(self.0[(row - 1, col)], '|' | '7' | 'F')
-                && !visited.contains(&(row - 1, col))
-            {
-                queue.push_back((row - 1, col));
-                visited.insert((row - 1, col));
-
-                if ch == 'S' {
-                    ['-', '7', 'F']
-                        .iter()
-                        .for_each(|&c| _ = potential_s.remove(&c));
-                }
-            }
+    /// Infers the pipe hidden under `S` from the neighbours connecting into it,
+    /// trying each candidate pair until one closes a loop back on `S`.
+    fn trace_loop(pipes: &Matrix<Pipe>) -> Result<(Pipe, Vec<(usize, usize)>), String> {
+        let start = pipes
+            .items()
+            .find(|&(_, &pipe)| pipe == Pipe::Start)
+            .ok_or("no start tile")?
+            .0;
+
+        Direction::ALL
+            .into_iter()
+            .filter(|&direction| {
+                pipes
+                    .move_in_direction(start, direction.offset())
+                    .is_some_and(|pos| pipes[pos].connects(direction.opposite()))
+            })
+            .tuple_combinations()
+            .filter_map(|(a, b)| Pipe::from_connections(a, b))
+            .find_map(|pipe| Some((pipe, Self::walk(pipes, start, pipe)?)))
+            .ok_or_else(|| format!("no loop through start tile at {start:?}"))
+    }
 
-            if row < self.0.rows - 1
-                && matches!(ch, 'S' | '|' | '7' | 'F')
-                && matches!(self.0[(row + 1, col)], '|' | 'J' | 'L')
-                && !visited.contains(&(row + 1, col))
-            {
-                queue.push_back((row + 1, col));
-                visited.insert((row + 1, col));
-
-                if ch == 'S' {
-                    ['-', 'J', 'L']
-                        .iter()
-                        .for_each(|&c| _ = potential_s.remove(&c));
-                }
+    fn walk(
+        pipes: &Matrix<Pipe>,
+        start: (usize, usize),
+        pipe: Pipe,
+    ) -> Option<Vec<(usize, usize)>> {
+        let [mut direction, end] = pipe.connections()?;
+        let mut path = vec![start];
+        let mut pos = start;
+
+        loop {
+            pos = pipes.move_in_direction(pos, direction.offset())?;
+            if pos == start {
+                return (direction.opposite() == end).then_some(path);
             }
 
-            if col > 0
-                && matches!(ch, 'S' | '-' | 'J' | '7')
-                && matches!(self.0[(row, col - 1)], '-' | 'L' | 'F')
-                && !visited.contains(&(row, col - 1))
-            {
-                queue.push_back((row, col - 1));
-                visited.insert((row, col - 1));
-
-                if ch == 'S' {
-                    ['|', 'L', 'F']
-                        .iter()
-                        .for_each(|&c| _ = potential_s.remove(&c));
-                }
-            }
+            path.push(pos);
+            let [a, b] = pipes[pos].connections()?;
+            direction = match direction.opposite() {
+                from if from == a => b,
+                from if from == b => a,
+                _ => return None,
+            };
+        }
+    }
 
-            if col < self.0.columns - 1
-                && matches!(ch, 'S' | '-' | 'L' | 'F')
-                && matches!(self.0[(row, col + 1)], '-' | 'J' | '7')
-                && !visited.contains(&(row, col + 1))
-            {
-                queue.push_back((row, col + 1));
-                visited.insert((row, col + 1));
-
-                if ch == 'S' {
-                    ['|', 'J', '7']
-                        .iter()
-                        .for_each(|&c| _ = potential_s.remove(&c));
-                }
-            }
+    /// Returns the pipe hidden under `S` and the loop tiles in walking order,
+    /// starting at `S`.
+    fn find_loop(&self) -> (Pipe, &[(usize, usize)]) {
+        (self.start_pipe, &self.path)
+    }
+
+    fn render(&self, format: Format) -> Vec<u8> {
+        match format {
+            Format::Text => self.render_text().into_bytes(),
+            Format::Svg => self.render_svg().into_bytes(),
+            Format::Ppm => self.render_ppm(),
         }
+    }
 
-        let s = potential_s.into_iter().next().unwrap();
-        (s, visited.into_iter().collect())
+    fn render_text(&self) -> String {
+        let tiles = self.classify();
+
+        (0..self.pipes.rows)
+            .map(|row| {
+                (0..self.pipes.columns)
+                    .map(|col| match tiles[(row, col)] {
+                        Tile::Outside => ' ',
+                        Tile::Inside => '▒',
+                        Tile::Loop => match self.pipe_at((row, col)) {
+                            Pipe::NorthWest => '┘',
+                            Pipe::NorthEast => '└',
+                            Pipe::SouthEast => '┌',
+                            Pipe::SouthWest => '┐',
+                            Pipe::Horizontal => '─',
+                            Pipe::Vertical => '│',
+                            pipe => pipe.into(),
+                        },
+                    })
+                    .chain(['\n'])
+                    .collect::<String>()
+            })
+            .collect()
     }
 
-    fn find_start(&self) -> (usize, usize) {
-        self.0.items().find(|&(_, c)| *c == 'S').unwrap().0
+    fn render_svg(&self) -> String {
+        let path = &self.path;
+        let tiles = self.classify();
+
+        let inside = tiles
+            .items()
+            .filter(|&(_, &tile)| tile == Tile::Inside)
+            .map(|((row, col), _)| {
+                format!(r##"  <rect x="{col}" y="{row}" width="1" height="1" fill="#4caf50"/>"##)
+            })
+            .join("\n");
+        let points = path
+            .iter()
+            .map(|(row, col)| format!("{}.5,{}.5", col, row))
+            .join(" ");
+
+        format!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {} {}">
+  <rect width="100%" height="100%" fill="#ffffff"/>
+{inside}
+  <polygon points="{points}" fill="none" stroke="#212121" stroke-width="0.3"/>
+</svg>
+"##,
+            self.pipes.columns, self.pipes.rows
+        )
     }
 
-    #[allow(dead_code)]
-    fn draw(&self, path: &[(usize, usize)]) -> Self {
-        let mut new_matrix = self.0.clone();
-
-        for p in path.iter() {
-            let ch = new_matrix.get_mut(*p).unwrap();
-
-            new_matrix[*p] = match *ch {
-                'J' => '┘',
-                'L' => '└',
-                'F' => '┌',
-                '7' => '┐',
-                '-' => '─',
-                '|' => '│',
-                _ => *ch,
+    fn render_ppm(&self) -> Vec<u8> {
+        const SCALE: usize = 3;
+        const LOOP: [u8; 3] = [255, 255, 255];
+        const INSIDE: [u8; 3] = [76, 175, 80];
+
+        let tiles = self.classify();
+        let (width, height) = (self.pipes.columns * SCALE, self.pipes.rows * SCALE);
+        let mut pixels = vec![[0; 3]; width * height];
+
+        for ((row, col), &tile) in tiles.items() {
+            let mut paint = |dr: usize, dc: usize, color| {
+                pixels[(row * SCALE + dr) * width + col * SCALE + dc] = color;
             };
-        }
 
-        new_matrix.items_mut().for_each(|(pos, ch)| {
-            if !path.contains(&pos) {
-                *ch = '.';
+            match tile {
+                Tile::Outside => {}
+                Tile::Inside => {
+                    (0..SCALE * SCALE).for_each(|i| paint(i / SCALE, i % SCALE, INSIDE))
+                }
+                Tile::Loop => {
+                    paint(1, 1, LOOP);
+                    for direction in self.pipe_at((row, col)).connections().unwrap() {
+                        let (dr, dc) = direction.offset();
+                        paint((1 + dr) as usize, (1 + dc) as usize, LOOP);
+                    }
+                }
             }
-        });
+        }
 
-        Self(new_matrix)
+        let mut image = format!("P6\n{width} {height}\n255\n").into_bytes();
+        image.extend(pixels.into_iter().flatten());
+        image
     }
 
-    #[allow(dead_code)]
-    fn print(&self) {
-        for row in 0..self.0.rows {
-            for col in 0..self.0.columns {
-                print!("{}", self.0[(row, col)]);
-            }
-            println!();
+    fn pipe_at(&self, pos: (usize, usize)) -> Pipe {
+        match self.pipes[pos] {
+            Pipe::Start => self.start_pipe,
+            pipe => pipe,
         }
     }
 
-    fn outside(&mut self, path: &[(usize, usize)]) -> HashSet<(usize, usize)> {
-        self.0.items_mut().for_each(|(pos, ch)| {
-            if !path.contains(&pos) {
-                *ch = '.';
-            }
-        });
+    fn enclosed_area(path: &[(usize, usize)]) -> usize {
+        let twice_area = path
+            .iter()
+            .circular_tuple_windows()
+            .map(|(&(r1, c1), &(r2, c2))| (c1 * r2) as isize - (c2 * r1) as isize)
+            .sum::<isize>()
+            .unsigned_abs();
 
-        let mut outside = HashSet::new();
+        // Pick's theorem: A = i + b/2 - 1
+        twice_area / 2 - path.len() / 2 + 1
+    }
 
-        for row in 0..self.0.rows {
-            let mut within = false;
-            let mut up = false;
+    fn classify(&self) -> Matrix<Tile> {
+        let path = self.path.iter().copied().collect::<HashSet<_>>();
 
-            for col in 0..self.0.columns {
-                let ch = self.0[(row, col)];
+        let mut tiles = Matrix::new(self.pipes.rows, self.pipes.columns, Tile::Outside);
+        for row in 0..self.pipes.rows {
+            let mut inside = false;
 
-                match ch {
-                    '|' => {
-                        within = !within;
-                    }
-                    'L' | 'F' => {
-                        up = ch == 'L';
-                    }
-                    '7' | 'J' => {
-                        if matches!((ch, up), ('7', true) | ('J', false)) {
-                            within = !within;
-                        }
-                        up = false;
-                    }
-                    '.' | '-' => {}
-                    _ => {
-                        unreachable!("Unknown character: {}", ch)
+            for col in 0..self.pipes.columns {
+                if !path.contains(&(row, col)) {
+                    if inside {
+                        tiles[(row, col)] = Tile::Inside;
                     }
+                    continue;
                 }
 
-                if !within {
-                    outside.insert((row, col));
+                let pipe = self.pipe_at((row, col));
+                tiles[(row, col)] = Tile::Loop;
+                if pipe.connects(Direction::North) {
+                    inside = !inside;
                 }
             }
         }
 
-        outside
+        tiles
     }
 }
 
+/// Usage: `day10 [--render text|svg|ppm] [--output FILE]`
 fn main() {
+    let args = env::args().collect::<Vec<_>>();
+    let flag = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .and_then(|index| args.get(index + 1))
+    };
+
+    if let Some(format) = flag("--render") {
+        let image = Grid::new(INPUT).render(format.parse().unwrap());
+
+        match flag("--output") {
+            Some(path) => fs::write(path, image).unwrap(),
+            None => io::stdout().write_all(&image).unwrap(),
+        }
+
+        return;
+    }
+
     println!("Part 1: {}", part1(INPUT));
     println!("Part 2: {}", part2(INPUT));
 }
 
 fn part1(input: impl AsRef<str>) -> usize {
-    Grid::new(input).find_path().1.len() / 2
+    Grid::new(input).find_loop().1.len() / 2
 }
 
 fn part2(input: impl AsRef<str>) -> usize {
-    let mut grid = Grid::new(input);
-    let (s, path) = grid.find_path();
-
-    let start = grid.find_start();
-    grid.0[start] = s;
-
-    let outside = grid.outside(&path);
-
-    grid.0.rows * grid.0.columns
-        - outside.len()
-        - path.iter().filter(|p| !outside.contains(p)).count()
+    Grid::enclosed_area(Grid::new(input).find_loop().1)
 }
 
 #[cfg(test)]
@@ -277,4 +459,105 @@ L7JLJL-JLJLJL--JLJ.L"
             10
         );
     }
+
+    #[test]
+    fn test_find_loop() {
+        let grid = Grid::new(
+            "7-F7-
+.FJ|7
+SJLL7
+|F--J
+LJ.LJ",
+        );
+        let (pipe, path) = grid.find_loop();
+
+        assert_eq!(pipe, Pipe::SouthEast);
+        assert_eq!(path.len(), 16);
+        assert_eq!(&path[..4], &[(2, 0), (3, 0), (4, 0), (4, 1)]);
+        assert!(path
+            .iter()
+            .circular_tuple_windows()
+            .all(|(a, b)| a.0.abs_diff(b.0) + a.1.abs_diff(b.1) == 1));
+    }
+
+    #[test]
+    fn test_unknown_tile() {
+        assert_eq!(
+            "S-7\n|X|\nL-J".parse::<Grid>().err(),
+            Some("unknown tile 'X' at (1, 1)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_start() {
+        // every neighbour of S points into it, but only the north-east pair closes a loop
+        let grid = Grid::new(
+            "..|..
+.F7..
+-SJ..
+.|...
+.....",
+        );
+        let (pipe, path) = grid.find_loop();
+
+        assert_eq!(pipe, Pipe::NorthEast);
+        assert_eq!(path, vec![(2, 1), (1, 1), (1, 2), (2, 2)]);
+
+        assert_eq!(
+            "...\n.S.\n...".parse::<Grid>().err(),
+            Some("no loop through start tile at (1, 1)".to_string())
+        );
+        assert_eq!(
+            "F-7\n|.|\nL-J".parse::<Grid>().err(),
+            Some("no start tile".to_string())
+        );
+    }
+
+    #[test]
+    fn test_classify() {
+        let input = ".F----7F7F7F7F-7....
+.|F--7||||||||FJ....
+.||.FJ||||||||L7....
+FJL7L7LJLJ||LJ.L-7..
+L--J.L7...LJS7F-7L7.
+....F-J..F7FJ|L7L7L7
+....L7.F7||L7|.L7L7|
+.....|FJLJ|FJ|F7|.LJ
+....FJL-7.||.||||...
+....L---J.LJ.LJLJ...";
+        let tiles = Grid::new(input).classify();
+        let count = |tile| tiles.values().filter(|&&t| t == tile).count();
+
+        assert_eq!(count(Tile::Inside), 8);
+        assert_eq!(count(Tile::Loop), part1(input) * 2);
+        assert_eq!(tiles[(0, 0)], Tile::Outside);
+        assert_eq!(tiles[(4, 7)], Tile::Inside);
+        assert_eq!(tiles[(4, 12)], Tile::Loop);
+    }
+
+    #[test]
+    fn test_render() {
+        let grid = Grid::new(
+            ".....
+.S-7.
+.|.|.
+.L-J.
+.....",
+        );
+
+        assert_eq!(
+            String::from_utf8(grid.render(Format::Text)).unwrap(),
+            "     \n ┌─┐ \n │▒│ \n └─┘ \n     \n"
+        );
+
+        let svg = String::from_utf8(grid.render(Format::Svg)).unwrap();
+        assert!(svg.contains(r#"viewBox="0 0 5 5""#));
+        assert!(svg.contains(r#"<rect x="2" y="2" width="1" height="1""#));
+        assert!(svg.contains(r#"points="1.5,1.5 1.5,2.5 1.5,3.5"#));
+
+        let ppm = grid.render(Format::Ppm);
+        let header = b"P6\n15 15\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(ppm.len(), header.len() + 15 * 15 * 3);
+    }
 }
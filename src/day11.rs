@@ -1,52 +1,160 @@
+use std::str::FromStr;
+
 use aoc::Point2D;
-use itertools::Itertools;
 use pathfinding::matrix::Matrix;
 
 const INPUT: &str = include_str!("../input/day11.txt");
 
-fn main() {
-    println!("Part 1: {}", solve(INPUT, 1));
-    println!("Part 2: {}", solve(INPUT, 1_000_000 - 1));
+struct Universe {
+    galaxies: Vec<Point2D<usize>>,
+    empty_rows: Vec<bool>,
+    empty_cols: Vec<bool>,
 }
 
-fn solve(input: impl AsRef<str>, gap_size: usize) -> usize {
-    let matrix = Matrix::from_iter(input.as_ref().lines().map(|line| line.chars()));
+impl FromStr for Universe {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let matrix = Matrix::from_iter(s.lines().map(|line| line.chars()));
+
+        let galaxies = matrix
+            .items()
+            .filter(|&(_, &c)| c == '#')
+            .map(|(pos, _)| Point2D::new(pos.1, pos.0))
+            .collect::<Vec<_>>();
+
+        let mut empty_rows = vec![true; matrix.rows];
+        let mut empty_cols = vec![true; matrix.columns];
+        for galaxy in &galaxies {
+            empty_rows[galaxy.y] = false;
+            empty_cols[galaxy.x] = false;
+        }
+
+        Ok(Self {
+            galaxies,
+            empty_rows,
+            empty_cols,
+        })
+    }
+}
 
-    let galaxies = matrix
-        .items()
-        .filter(|&(_, &c)| (c == '#'))
-        .map(|(pos, _)| Point2D::new(pos.1, pos.0))
-        .collect::<Vec<_>>();
+impl Universe {
+    /// Every empty row and column becomes `factor` rows or columns, so `1`
+    /// leaves the universe unchanged and `2` doubles the gaps.
+    fn expand(&self, factor: usize) -> Expanded {
+        self.expand_axes(factor, factor)
+    }
 
-    let empty_rows = (0..matrix.rows)
-        .filter(|&row| (0..matrix.columns).all(|col| matrix.get((row, col)) == Some(&'.')))
-        .collect::<Vec<_>>();
+    fn expand_axes(&self, x_factor: usize, y_factor: usize) -> Expanded {
+        let cols = expansion(&self.empty_cols, x_factor);
+        let rows = expansion(&self.empty_rows, y_factor);
 
-    let empty_cols = (0..matrix.columns)
-        .filter(|&col| (0..matrix.rows).all(|row| matrix.get((row, col)) == Some(&'.')))
-        .collect::<Vec<_>>();
+        Expanded {
+            galaxies: self
+                .galaxies
+                .iter()
+                .map(|galaxy| Point2D::new(cols[galaxy.x], rows[galaxy.y]))
+                .collect(),
+        }
+    }
+}
 
-    galaxies
+/// Maps every row (or column) index to its expanded coordinate, using a
+/// running count of the empty lines before it.
+fn expansion(empty: &[bool], factor: usize) -> Vec<usize> {
+    empty
         .iter()
-        .combinations(2)
-        .map(|pair| {
-            pair[0].manhattan(pair[1])
-                + gap_size
-                    * (gaps(pair[0].x, pair[1].x, &empty_cols)
-                        + gaps(pair[0].y, pair[1].y, &empty_rows))
+        .scan(0, |expanded, &is_empty| {
+            let pos = *expanded;
+            *expanded += if is_empty { factor } else { 1 };
+            Some(pos)
         })
-        .sum()
+        .collect()
+}
+
+/// Galaxies are indexed from 0 in reading order.
+struct Expanded {
+    galaxies: Vec<Point2D<usize>>,
 }
 
-#[inline]
-fn gaps(a: usize, b: usize, empty: &[usize]) -> usize {
-    let (min, max) = (a.min(b), a.max(b));
-    empty.iter().filter(|&&pos| min < pos && pos < max).count()
+impl Expanded {
+    #[allow(dead_code)]
+    fn distance(&self, a: usize, b: usize) -> usize {
+        self.galaxies[a].manhattan(&self.galaxies[b])
+    }
+
+    fn distance_sum(&self) -> usize {
+        distance_sum(self.galaxies.iter().map(|g| g.x).collect())
+            + distance_sum(self.galaxies.iter().map(|g| g.y).collect())
+    }
+
+    /// Returns `(a, b, distance)` for the closest pair of galaxies.
+    #[allow(dead_code)]
+    fn nearest_pair(&self) -> Option<(usize, usize, usize)> {
+        let mut order = (0..self.galaxies.len()).collect::<Vec<_>>();
+        order.sort_unstable_by_key(|&i| self.galaxies[i].x);
+
+        let mut best: Option<(usize, usize, usize)> = None;
+        for (n, &a) in order.iter().enumerate() {
+            for &b in &order[n + 1..] {
+                let dx = self.galaxies[b].x - self.galaxies[a].x;
+                if best.is_some_and(|(_, _, distance)| dx > distance) {
+                    break;
+                }
+
+                let pair = (a.min(b), a.max(b), self.distance(a, b));
+                if best.is_none_or(|best| (pair.2, pair.0, pair.1) < (best.2, best.0, best.1)) {
+                    best = Some(pair);
+                }
+            }
+        }
+
+        best
+    }
+
+    /// Returns `(a, b, distance)` for the most distant pair of galaxies, using
+    /// `|dx| + |dy| = max(|d(x + y)|, |d(x - y)|)`.
+    #[allow(dead_code)]
+    fn farthest_pair(&self) -> Option<(usize, usize, usize)> {
+        [
+            |g: &Point2D<usize>| (g.x + g.y) as isize,
+            |g: &Point2D<usize>| g.x as isize - g.y as isize,
+        ]
+        .iter()
+        .filter_map(|key| {
+            let keys = self.galaxies.iter().map(key).enumerate();
+            let (min, _) = keys.clone().min_by_key(|&(_, k)| k)?;
+            let (max, _) = keys.max_by_key(|&(_, k)| k)?;
+
+            Some((min.min(max), min.max(max), self.distance(min, max)))
+        })
+        .max_by_key(|&(_, _, distance)| distance)
+        .filter(|&(a, b, _)| a != b)
+    }
+}
+
+/// Sum of `|a - b|` over all pairs, in `O(n log n)`.
+fn distance_sum(mut coords: Vec<usize>) -> usize {
+    coords.sort_unstable();
+
+    coords
+        .iter()
+        .enumerate()
+        .fold((0, 0), |(sum, prefix), (i, &coord)| {
+            (sum + coord * i - prefix, prefix + coord)
+        })
+        .0
+}
+
+fn main() {
+    let universe = Universe::from_str(INPUT).unwrap();
+    println!("Part 1: {}", universe.expand(2).distance_sum());
+    println!("Part 2: {}", universe.expand(1_000_000).distance_sum());
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::solve;
+    use crate::{distance_sum, Universe};
 
     const INPUT: &str = "...#......
 .......#..
@@ -59,14 +167,65 @@ mod tests {
 .......#..
 #...#.....";
 
+    fn solve(input: &str, factor: usize) -> usize {
+        input
+            .parse::<Universe>()
+            .unwrap()
+            .expand(factor)
+            .distance_sum()
+    }
+
     #[test]
     fn test_part1() {
-        assert_eq!(solve(INPUT, 1), 374);
+        assert_eq!(solve(INPUT, 2), 374);
     }
 
     #[test]
     fn test_part2() {
-        assert_eq!(solve(INPUT, 10 - 1), 1030);
-        assert_eq!(solve(INPUT, 100 - 1), 8410);
+        assert_eq!(solve(INPUT, 10), 1030);
+        assert_eq!(solve(INPUT, 100), 8410);
+    }
+
+    #[test]
+    fn test_distance_sum() {
+        assert_eq!(distance_sum(vec![]), 0);
+        assert_eq!(distance_sum(vec![5, 1, 3]), 8);
+    }
+
+    #[test]
+    fn test_many_galaxies() {
+        // 300 rows of 67 galaxies each, every column between them is empty
+        let row = (0..200)
+            .map(|i| if i % 3 == 0 { '#' } else { '.' })
+            .collect::<String>();
+        let input = vec![row; 300].join("\n");
+
+        assert_eq!(solve(&input, 2), 42_752_475_550);
+    }
+
+    #[test]
+    fn test_queries() {
+        let universe = INPUT.parse::<Universe>().unwrap();
+        let expanded = universe.expand(2);
+
+        assert_eq!(expanded.distance(4, 8), 9);
+        assert_eq!(expanded.distance(0, 6), 15);
+        assert_eq!(expanded.distance(2, 5), 17);
+        assert_eq!(expanded.distance(7, 8), 5);
+        assert_eq!(expanded.nearest_pair(), Some((1, 3, 5)));
+        assert_eq!(expanded.farthest_pair(), Some((1, 7, 19)));
+
+        assert_eq!(universe.expand(1).distance_sum(), 292);
+        assert_eq!(universe.expand_axes(2, 1).distance_sum(), 338);
+        assert_eq!(universe.expand_axes(2, 1).distance(0, 8), 10);
+    }
+
+    #[test]
+    fn test_single_galaxy() {
+        let expanded = "..\n.#".parse::<Universe>().unwrap().expand(2);
+
+        assert_eq!(expanded.nearest_pair(), None);
+        assert_eq!(expanded.farthest_pair(), None);
+        assert_eq!(expanded.distance_sum(), 0);
     }
 }
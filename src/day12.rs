@@ -1,77 +1,310 @@
-use aoc::parse_nums;
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    ops::{AddAssign, Mul},
+    str::FromStr,
+};
+
+use derive_more::Display;
+use num::{One, Zero};
+use rayon::prelude::*;
 
 const INPUT: &str = include_str!("../input/day12.txt");
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Spring {
+    Operational,
+    Damaged,
+    Unknown,
+}
+
+impl TryFrom<char> for Spring {
+    type Error = char;
+
+    fn try_from(c: char) -> Result<Self, Self::Error> {
+        match c {
+            '.' => Ok(Spring::Operational),
+            '#' => Ok(Spring::Damaged),
+            '?' => Ok(Spring::Unknown),
+            c => Err(c),
+        }
+    }
+}
+
+#[derive(Debug, Display, PartialEq)]
+enum RecordError {
+    #[display("expected springs and groups separated by a space")]
+    MissingSeparator,
+    #[display("unknown spring {_0:?} at column {_1}")]
+    UnknownSpring(char, usize),
+    #[display("invalid group size {_0:?}")]
+    InvalidGroup(String),
+    #[display("groups need at least {needed} springs, pattern has {len}")]
+    TooShort { needed: usize, len: usize },
+}
+
+#[derive(Debug, Display, PartialEq)]
+#[display("line {line}: {error}")]
+struct LineError {
+    line: usize,
+    error: RecordError,
+}
+
 #[derive(Debug)]
 struct Record {
-    pattern: Vec<char>,
+    pattern: Vec<Spring>,
     rule: Vec<usize>,
 }
 
-impl Record {
-    fn new(input: impl AsRef<str>) -> Self {
-        let (pattern, rule) = input
-            .as_ref()
-            .split_once(' ')
-            .map(|(pattern, rule)| (pattern.chars().collect(), parse_nums(rule)))
-            .unwrap();
+impl FromStr for Record {
+    type Err = RecordError;
 
-        Self { pattern, rule }
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pattern, rule) = s.split_once(' ').ok_or(RecordError::MissingSeparator)?;
+
+        let pattern = pattern
+            .chars()
+            .enumerate()
+            .map(|(column, c)| {
+                Spring::try_from(c).map_err(|c| RecordError::UnknownSpring(c, column))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // an empty rule means no damaged groups, but every listed group must be a size
+        let rule = rule
+            .split(',')
+            .filter(|_| !rule.is_empty())
+            .map(|group| match group.parse() {
+                Ok(0) | Err(_) => Err(RecordError::InvalidGroup(group.to_string())),
+                Ok(size) => Ok(size),
+            })
+            .collect::<Result<Vec<usize>, _>>()?;
+
+        let needed = rule.iter().sum::<usize>() + rule.len().saturating_sub(1);
+        if needed > pattern.len() {
+            return Err(RecordError::TooShort {
+                needed,
+                len: pattern.len(),
+            });
+        }
+
+        Ok(Self { pattern, rule })
     }
+}
+
+fn parse_records(input: &str) -> Result<Vec<Record>, LineError> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(line, l)| {
+            l.parse().map_err(|error| LineError {
+                line: line + 1,
+                error,
+            })
+        })
+        .collect()
+}
 
+impl Record {
     fn count(&self) -> usize {
-        Self::count_inner(&self.pattern, &self.rule, &mut HashMap::new())
+        self.table()[0][0]
     }
 
-    fn count_inner<'a>(
-        pat: &'a [char],
-        nums: &'a [usize],
-        cache: &mut HashMap<(&'a [char], &'a [usize]), usize>,
-    ) -> usize {
-        if pat.is_empty() {
-            return nums.is_empty() as usize;
-        }
+    /// `table[i][j]` is the number of ways to place groups `j..` into
+    /// `pattern[i..]`.
+    fn table(&self) -> Vec<Vec<usize>> {
+        let (n, k) = (self.pattern.len(), self.rule.len());
+        let mut table = vec![vec![0; k + 1]; n + 1];
+        table[n][k] = 1;
 
-        if nums.is_empty() {
-            return !pat.contains(&'#') as usize;
-        }
+        for i in (0..n).rev() {
+            for j in 0..=k {
+                if self.can_skip(i) {
+                    table[i][j] += table[i + 1][j];
+                }
 
-        if let Some(&count) = cache.get(&(pat, nums)) {
-            return count;
+                if j < k && self.can_place(i, self.rule[j]) {
+                    table[i][j] += table[(i + self.rule[j] + 1).min(n)][j + 1];
+                }
+            }
         }
 
-        let mut result = 0;
-        if matches!(pat[0], '.' | '?') {
-            result += Self::count_inner(&pat[1..], nums, cache);
-        }
+        table
+    }
 
-        if matches!(pat[0], '#' | '?')
-            && nums[0] <= pat.len()
-            && !pat[..nums[0]].contains(&'.')
-            && (nums[0] == pat.len() || pat[nums[0]] != '#')
-        {
-            let slice = if nums[0] + 1 >= pat.len() {
-                &[]
-            } else {
-                &pat[nums[0] + 1..]
-            };
-
-            result += Self::count_inner(slice, &nums[1..], cache)
-        }
+    fn can_skip(&self, i: usize) -> bool {
+        matches!(self.pattern[i], Spring::Operational | Spring::Unknown)
+    }
 
-        cache.insert((pat, nums), result);
+    fn can_place(&self, i: usize, size: usize) -> bool {
+        i + size <= self.pattern.len()
+            && !self.pattern[i..i + size].contains(&Spring::Operational)
+            && self.pattern.get(i + size) != Some(&Spring::Damaged)
+    }
 
-        result
+    #[allow(dead_code)]
+    fn arrangements(&self) -> Arrangements<'_> {
+        Arrangements {
+            record: self,
+            table: self.table(),
+            stack: vec![(0, 0, Vec::new())],
+        }
     }
 
     fn unfold(&self, times: usize) -> Self {
         let mut pattern = self.pattern.repeat(times);
-        (1..times).for_each(|i| pattern.insert(i * self.pattern.len() + i - 1, '?'));
+        (1..times).for_each(|i| pattern.insert(i * self.pattern.len() + i - 1, Spring::Unknown));
         let rule = self.rule.repeat(times);
 
         Self { pattern, rule }
     }
+
+    /// Counts the arrangements of `unfold(times)` without building it, by
+    /// applying the transfer operator of a single `?`-prefixed copy `times`
+    /// times. States are `(group, run)`: the index of the current group and
+    /// how many of its springs are already placed.
+    #[allow(dead_code)]
+    fn count_unfolded<T>(&self, times: usize) -> T
+    where
+        T: Clone + Zero + One + AddAssign + Mul<Output = T>,
+    {
+        let k = self.rule.len();
+        if k == 0 || times == 0 {
+            let damaged = times > 0 && self.pattern.contains(&Spring::Damaged);
+            return if damaged { T::zero() } else { T::one() };
+        }
+
+        let total = k * times;
+        let run = *self.rule.iter().max().unwrap() + 1;
+
+        let mut separated = vec![Spring::Unknown];
+        separated.extend(&self.pattern);
+        let first = self.transfer::<T>(&self.pattern);
+        let next = self.transfer::<T>(&separated);
+
+        let mut states = vec![vec![T::zero(); run]; total + 1];
+        states[0][0] = T::one();
+
+        for copy in 0..times {
+            let transfer = if copy == 0 { &first } else { &next };
+            let mut updated = vec![vec![T::zero(); run]; total + 1];
+
+            for (group, runs) in states.iter().enumerate() {
+                for (r, count) in runs.iter().enumerate().filter(|(_, c)| !c.is_zero()) {
+                    for &(advanced, to, ref ways) in &transfer[group % k][r] {
+                        let group = group + advanced;
+                        if group < total || (group == total && to == 0) {
+                            updated[group][to] += count.clone() * ways.clone();
+                        }
+                    }
+                }
+            }
+
+            states = updated;
+        }
+
+        let mut count = states[total][0].clone();
+        count += states[total - 1][self.rule[k - 1]].clone();
+        count
+    }
+
+    /// For every start state `(group % k, run)`, lists `(groups advanced,
+    /// run, ways)` after reading `segment`.
+    #[allow(dead_code, clippy::type_complexity)]
+    fn transfer<T>(&self, segment: &[Spring]) -> Vec<Vec<Vec<(usize, usize, T)>>>
+    where
+        T: Clone + Zero + One + AddAssign,
+    {
+        let k = self.rule.len();
+        let size = |group: usize| self.rule[group % k];
+
+        (0..k)
+            .map(|start| {
+                (0..=size(start))
+                    .map(|run| {
+                        let mut states = HashMap::from([((0, run), T::one())]);
+
+                        for &spring in segment {
+                            let mut updated = HashMap::new();
+
+                            for ((advanced, run), ways) in states {
+                                let group = start + advanced;
+                                let mut step = |state| {
+                                    *updated.entry(state).or_insert_with(T::zero) += ways.clone()
+                                };
+
+                                if matches!(spring, Spring::Operational | Spring::Unknown) {
+                                    if run == 0 {
+                                        step((advanced, 0));
+                                    } else if run == size(group) {
+                                        step((advanced + 1, 0));
+                                    }
+                                }
+
+                                if matches!(spring, Spring::Damaged | Spring::Unknown)
+                                    && run < size(group)
+                                {
+                                    step((advanced, run + 1));
+                                }
+                            }
+
+                            states = updated;
+                        }
+
+                        states
+                            .into_iter()
+                            .map(|((advanced, run), ways)| (advanced, run, ways))
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Yields every valid arrangement of a record as a pattern of `.` and `#`,
+/// only descending into branches the DP table says are non-empty.
+#[allow(dead_code)]
+struct Arrangements<'a> {
+    record: &'a Record,
+    table: Vec<Vec<usize>>,
+    stack: Vec<(usize, usize, Vec<char>)>,
+}
+
+impl Iterator for Arrangements<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (n, k) = (self.record.pattern.len(), self.record.rule.len());
+
+        while let Some((i, j, prefix)) = self.stack.pop() {
+            if i == n {
+                if j == k {
+                    return Some(prefix.into_iter().collect());
+                }
+                continue;
+            }
+
+            if self.record.can_skip(i) && self.table[i + 1][j] > 0 {
+                let mut prefix = prefix.clone();
+                prefix.push('.');
+                self.stack.push((i + 1, j, prefix));
+            }
+
+            if j < k && self.record.can_place(i, self.record.rule[j]) {
+                let size = self.record.rule[j];
+                let next = (i + size + 1).min(n);
+
+                if self.table[next][j + 1] > 0 {
+                    let mut prefix = prefix;
+                    prefix.extend(std::iter::repeat_n('#', size));
+                    prefix.extend(std::iter::repeat_n('.', next - i - size));
+                    self.stack.push((next, j + 1, prefix));
+                }
+            }
+        }
+
+        None
+    }
 }
 
 fn main() {
@@ -80,20 +313,30 @@ fn main() {
 }
 
 fn part1(input: impl AsRef<str>) -> usize {
-    input.as_ref().lines().map(|l| Record::new(l).count()).sum()
+    parse_records(input.as_ref())
+        .unwrap()
+        .par_iter()
+        .map(Record::count)
+        .sum()
 }
 
 fn part2(input: impl AsRef<str>) -> usize {
-    input
-        .as_ref()
-        .lines()
-        .map(|l| Record::new(l).unfold(5).count())
+    parse_records(input.as_ref())
+        .unwrap()
+        .par_iter()
+        .map(|record| record.unfold(5).count())
         .sum()
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{part1, Record};
+    use num::BigUint;
+
+    use crate::{parse_records, part1, LineError, Record, RecordError};
+
+    fn parse(line: &str) -> Record {
+        line.parse().unwrap()
+    }
 
     const INPUT: &str = "???.### 1,1,3
 .??..??...?##. 1,1,3
@@ -104,23 +347,120 @@ mod tests {
 
     #[test]
     fn test_part1() {
-        assert_eq!(Record::new("???.### 1,1,3").count(), 1);
-        assert_eq!(Record::new(".??..??...?##. 1,1,3").count(), 4);
-        assert_eq!(Record::new("?#?#?#?#?#?#?#? 1,3,1,6").count(), 1);
+        assert_eq!(parse("???.### 1,1,3").count(), 1);
+        assert_eq!(parse(".??..??...?##. 1,1,3").count(), 4);
+        assert_eq!(parse("?#?#?#?#?#?#?#? 1,3,1,6").count(), 1);
         assert_eq!(part1(INPUT), 21);
     }
 
     #[test]
     fn test_part2() {
-        assert_eq!(Record::new("???.### 1,1,3").unfold(5).count(), 1);
-        assert_eq!(Record::new(".??..??...?##. 1,1,3").unfold(5).count(), 16384);
-        assert_eq!(Record::new("?#?#?#?#?#?#?#? 1,3,1,6").unfold(5).count(), 1);
-        assert_eq!(Record::new("????.#...#... 4,1,1").unfold(5).count(), 16);
+        assert_eq!(parse("???.### 1,1,3").unfold(5).count(), 1);
+        assert_eq!(parse(".??..??...?##. 1,1,3").unfold(5).count(), 16384);
+        assert_eq!(parse("?#?#?#?#?#?#?#? 1,3,1,6").unfold(5).count(), 1);
+        assert_eq!(parse("????.#...#... 4,1,1").unfold(5).count(), 16);
+        assert_eq!(parse("????.######..#####. 1,6,5").unfold(5).count(), 2500);
+
+        assert_eq!(parse("?###???????? 3,2,1").unfold(5).count(), 506250);
+    }
+
+    #[test]
+    fn test_arrangements() {
+        let record = parse("?###???????? 3,2,1");
+        let arrangements = record.arrangements().collect::<Vec<_>>();
+
+        assert_eq!(arrangements.len(), 10);
+        assert!(arrangements.contains(&".###.##.#...".to_string()));
+        assert!(arrangements.contains(&".###....##.#".to_string()));
+
+        for line in INPUT.lines() {
+            let record = parse(line);
+            assert_eq!(record.arrangements().count(), record.count());
+        }
+    }
+
+    #[test]
+    fn test_long_pattern() {
+        let record = parse(&format!("{} 1", "?".repeat(100_000)));
+        assert_eq!(record.count(), 100_000);
+    }
+
+    #[test]
+    fn test_count_unfolded() {
+        for line in INPUT.lines() {
+            let record = parse(line);
+
+            for times in 0..=5 {
+                assert_eq!(
+                    record.count_unfolded::<usize>(times),
+                    record.unfold(times).count()
+                );
+            }
+        }
+
+        assert_eq!(parse("#.? ").count_unfolded::<usize>(3), 0);
+        assert_eq!(parse("?.? ").count_unfolded::<usize>(3), 1);
+    }
+
+    #[test]
+    fn test_count_unfolded_large() {
+        let record = parse(".??..??...?##. 1,1,3");
+        assert_eq!(record.count_unfolded::<u128>(40), 1 << 119);
+        assert_eq!(
+            record.count_unfolded::<BigUint>(50),
+            BigUint::from(2u32).pow(149)
+        );
+
+        let record = parse("?###???????? 3,2,1");
+        assert_eq!(
+            record.count_unfolded::<u128>(7),
+            record.unfold(7).count() as u128
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |line: &str| line.parse::<Record>().err();
+
+        assert_eq!(error("???.###"), Some(RecordError::MissingSeparator));
         assert_eq!(
-            Record::new("????.######..#####. 1,6,5").unfold(5).count(),
-            2500
+            error("??x.### 1,1,3"),
+            Some(RecordError::UnknownSpring('x', 2))
         );
+        assert_eq!(
+            error("???.### 1,a,3"),
+            Some(RecordError::InvalidGroup("a".to_string()))
+        );
+        assert_eq!(
+            error("??? 1,,1"),
+            Some(RecordError::InvalidGroup(String::new()))
+        );
+        assert_eq!(
+            error("??? 1,"),
+            Some(RecordError::InvalidGroup(String::new()))
+        );
+        assert_eq!(
+            error("?? 0"),
+            Some(RecordError::InvalidGroup("0".to_string()))
+        );
+        assert_eq!(
+            error("???.## 1,1,3"),
+            Some(RecordError::TooShort { needed: 7, len: 6 })
+        );
+        assert!(error("???.### 1,1,3").is_none());
 
-        assert_eq!(Record::new("?###???????? 3,2,1").unfold(5).count(), 506250);
+        assert_eq!(
+            parse_records("???.### 1,1,3\n?? 1,1").err(),
+            Some(LineError {
+                line: 2,
+                error: RecordError::TooShort { needed: 3, len: 2 }
+            })
+        );
+        assert_eq!(
+            parse_records("???.### 1,1,3\n?? 1,1")
+                .unwrap_err()
+                .to_string(),
+            "line 2: groups need at least 3 springs, pattern has 2"
+        );
     }
 }
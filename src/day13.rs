@@ -1,85 +1,183 @@
-use num::Integer;
+use std::str::FromStr;
+
+use derive_more::Display;
 
 const INPUT: &str = include_str!("../input/day13.txt");
 
+#[derive(Debug, Display, PartialEq)]
+enum FrameError {
+    #[display("frame is empty")]
+    Empty,
+    #[display("row {_0} has a different width than the first row")]
+    Ragged(usize),
+    #[display("frame of {rows}x{cols} cells exceeds the 64x64 limit")]
+    TooLarge { rows: usize, cols: usize },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Axis {
+    /// Reflection line below the given number of rows.
+    Horizontal(usize),
+    /// Reflection line right of the given number of columns.
+    Vertical(usize),
+}
+
+impl Axis {
+    fn score(self) -> usize {
+        match self {
+            Axis::Horizontal(rows) => rows * 100,
+            Axis::Vertical(cols) => cols,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Smudge {
+    axis: Axis,
+    cell: (usize, usize),
+}
+
+/// Rows and columns packed into bitmasks, so frames can be at most 64 cells
+/// wide and tall; larger frames are rejected when parsing.
 struct Frame {
-    rows: Vec<Vec<u8>>,
-    cols: Vec<Vec<u8>>,
+    rows: Vec<u64>,
+    cols: Vec<u64>,
 }
 
-impl Frame {
-    fn new(input: impl AsRef<str>) -> Self {
-        let rows = input
-            .as_ref()
+impl FromStr for Frame {
+    type Err = FrameError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let cells = s
             .lines()
-            .map(|line| line.as_bytes().to_vec())
+            .map(|line| line.bytes().map(|b| b == b'#').collect::<Vec<_>>())
             .collect::<Vec<_>>();
 
-        let cols = (0..rows[0].len())
-            .map(|col| rows.iter().map(|row| row[col]).collect::<Vec<_>>())
-            .collect::<Vec<_>>();
+        let width = cells.first().map_or(0, Vec::len);
+        if width == 0 {
+            return Err(FrameError::Empty);
+        }
+        if let Some(row) = cells.iter().position(|row| row.len() != width) {
+            return Err(FrameError::Ragged(row));
+        }
+        if cells.len() > u64::BITS as usize || width > u64::BITS as usize {
+            return Err(FrameError::TooLarge {
+                rows: cells.len(),
+                cols: width,
+            });
+        }
+
+        let rows = cells
+            .iter()
+            .map(|row| Self::pack(row.iter().copied()))
+            .collect();
+        let cols = (0..width)
+            .map(|col| Self::pack(cells.iter().map(|row| row[col])))
+            .collect();
 
-        Self { rows, cols }
+        Ok(Self { rows, cols })
     }
+}
 
-    fn mirror_value(&self) -> usize {
-        self.find(&self.rows, 0, None)
-            .map(|idx| (idx + 1) * 100)
-            .or_else(|| self.find(&self.cols, 0, None).map(|idx| idx + 1))
-            .unwrap_or_default()
+impl Frame {
+    fn new(input: impl AsRef<str>) -> Self {
+        input.as_ref().parse().unwrap()
     }
 
-    #[inline]
-    fn diff<'a, T: PartialEq>(a: &'a [T], b: &'a [T]) -> usize {
-        a.iter().zip(b.iter()).filter(|(a, b)| a != b).count()
+    fn pack(cells: impl Iterator<Item = bool>) -> u64 {
+        cells.fold(0, |mask, cell| mask << 1 | cell as u64)
     }
 
-    #[inline]
-    fn is_reflective<T: PartialEq>(s: &[T]) -> bool {
-        s.len().is_even() && s.iter().zip(s.iter().rev()).all(|(a, b)| a == b)
+    /// Number of differing cells when folding `lines` after the first `n`.
+    fn smudges(lines: &[u64], n: usize) -> usize {
+        lines[..n]
+            .iter()
+            .rev()
+            .zip(&lines[n..])
+            .map(|(a, b)| (a ^ b).count_ones() as usize)
+            .sum()
+    }
+
+    fn find_reflections(&self, smudges: usize) -> Vec<Axis> {
+        let axes = |lines: &[u64], axis: fn(usize) -> Axis| {
+            (1..lines.len())
+                .filter(|&n| Self::smudges(lines, n) == smudges)
+                .map(axis)
+                .collect::<Vec<_>>()
+        };
+
+        let mut reflections = axes(&self.rows, Axis::Horizontal);
+        reflections.extend(axes(&self.cols, Axis::Vertical));
+        reflections
+    }
+
+    fn mirror_value(&self) -> usize {
+        self.find_reflections(0)
+            .first()
+            .map(|axis| axis.score())
+            .unwrap_or_default()
     }
 
     fn mirror_value_with_smudge(&self) -> usize {
-        self.find(&self.rows, 1, self.find(&self.rows, 0, None))
-            .map(|idx| (idx + 1) * 100)
-            .or_else(|| {
-                self.find(&self.cols, 1, self.find(&self.cols, 0, None))
-                    .map(|idx| idx + 1)
-            })
+        self.find_smudge()
+            .map(|smudge| smudge.axis.score())
             .unwrap_or_default()
     }
 
-    fn find<'a, T: 'a + PartialEq>(
-        &'a self,
-        items: &[Vec<T>],
-        allowed_diff: usize,
-        ignore: Option<usize>,
-    ) -> Option<usize> {
-        let items_len = items.len();
-
-        (0..items_len)
-            .flat_map(|i| (i + 1..items_len).map(move |j| (i, j)))
-            .filter(|&(i, j)| {
-                Self::diff(&items[i], &items[j]) == allowed_diff
-                    && Self::is_reflective(&items[i + 1..j])
-                    && Some((j + i) / 2) != ignore
-            })
-            .find_map(|(i, j)| {
-                let (mut ii, mut jj) = (i, j);
+    fn is_set(&self, row: usize, col: usize) -> bool {
+        self.rows[row] >> (self.cols.len() - 1 - col) & 1 == 1
+    }
+
+    /// First reflection that needs exactly one flipped cell, together with
+    /// that cell as `(row, col)`.
+    fn find_smudge(&self) -> Option<Smudge> {
+        let axis = *self.find_reflections(1).first()?;
+        let (lines, n) = match axis {
+            Axis::Horizontal(n) => (&self.rows, n),
+            Axis::Vertical(n) => (&self.cols, n),
+        };
+
+        let (line, bits) = (0..n.min(lines.len() - n))
+            .map(|d| (n - 1 - d, lines[n - 1 - d] ^ lines[n + d]))
+            .find(|&(_, bits)| bits != 0)?;
+        let other = (bits.trailing_zeros() as usize, line);
+
+        let cell = match axis {
+            Axis::Horizontal(_) => (line, self.cols.len() - 1 - other.0),
+            Axis::Vertical(_) => (self.rows.len() - 1 - other.0, line),
+        };
+
+        Some(Smudge { axis, cell })
+    }
 
-                loop {
-                    if i != ii && jj != j && items[ii] != items[jj] {
-                        return None;
-                    }
+    /// Draws the frame with the mirror line (`─` or `│`) and the smudged cell
+    /// (`X`), if any.
+    fn render(&self, smudge: Option<&Smudge>) -> String {
+        let axis = smudge.map(|smudge| smudge.axis);
+        let mut out = String::new();
 
-                    if ii == 0 || jj == items_len - 1 {
-                        return Some((j + i) / 2);
-                    }
+        for row in 0..self.rows.len() {
+            if axis == Some(Axis::Horizontal(row)) {
+                out.push_str(&"─".repeat(self.cols.len()));
+                out.push('\n');
+            }
 
-                    ii -= 1;
-                    jj += 1;
+            for col in 0..self.cols.len() {
+                if axis == Some(Axis::Vertical(col)) {
+                    out.push('│');
                 }
-            })
+
+                out.push(match smudge {
+                    Some(smudge) if smudge.cell == (row, col) => 'X',
+                    _ if self.is_set(row, col) => '#',
+                    _ => '.',
+                });
+            }
+
+            out.push('\n');
+        }
+
+        out
     }
 }
 
@@ -96,13 +194,19 @@ where
 }
 
 fn main() {
+    for frame in INPUT.split("\n\n").map(Frame::new) {
+        if frame.find_smudge().is_none() {
+            eprintln!("No smudged reflection found:\n{}", frame.render(None));
+        }
+    }
+
     println!("Part 1: {}", solve(INPUT, Frame::mirror_value));
     println!("Part 2: {}", solve(INPUT, Frame::mirror_value_with_smudge));
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{solve, Frame};
+    use crate::{solve, Axis, Frame, FrameError, Smudge};
 
     const INPUT: &str = "#.##..##.
 ..#.##.#.
@@ -155,4 +259,96 @@ mod tests {
         assert_eq!(solve(INPUT2, Frame::mirror_value_with_smudge), 1000);
         assert_eq!(solve(INPUT3, Frame::mirror_value_with_smudge), 5);
     }
+
+    #[test]
+    fn test_find_reflections() {
+        let frames = INPUT.split("\n\n").map(Frame::new).collect::<Vec<_>>();
+
+        assert_eq!(frames[0].find_reflections(0), vec![Axis::Vertical(5)]);
+        assert_eq!(frames[1].find_reflections(0), vec![Axis::Horizontal(4)]);
+        assert_eq!(frames[0].find_reflections(1), vec![Axis::Horizontal(3)]);
+        assert_eq!(frames[1].find_reflections(1), vec![Axis::Horizontal(1)]);
+
+        let frame = Frame::new("##\n##");
+        assert_eq!(
+            frame.find_reflections(0),
+            vec![Axis::Horizontal(1), Axis::Vertical(1)]
+        );
+        assert!(frame.find_reflections(1).is_empty());
+    }
+
+    #[test]
+    fn test_find_smudge() {
+        let frames = INPUT.split("\n\n").map(Frame::new).collect::<Vec<_>>();
+
+        assert_eq!(
+            frames[0].find_smudge(),
+            Some(Smudge {
+                axis: Axis::Horizontal(3),
+                cell: (0, 0)
+            })
+        );
+        assert_eq!(
+            frames[1].find_smudge(),
+            Some(Smudge {
+                axis: Axis::Horizontal(1),
+                cell: (0, 4)
+            })
+        );
+        assert_eq!(
+            Frame::new("#..\n..#\n##.\n..#").find_smudge(),
+            Some(Smudge {
+                axis: Axis::Vertical(1),
+                cell: (0, 0)
+            })
+        );
+        assert_eq!(
+            Frame::new("#.\n..").find_smudge().map(|s| s.axis),
+            Some(Axis::Horizontal(1))
+        );
+        assert_eq!(Frame::new("#.\n.#").find_smudge(), None);
+    }
+
+    #[test]
+    fn test_render() {
+        let frame = Frame::new(INPUT.split("\n\n").next().unwrap());
+
+        assert_eq!(
+            frame.render(frame.find_smudge().as_ref()),
+            "X.##..##.
+..#.##.#.
+##......#
+─────────
+##......#
+..#.##.#.
+..##..##.
+#.#.##.#.
+"
+        );
+
+        let frame = Frame::new("#..\n..#\n##.\n..#");
+        assert_eq!(
+            frame.render(frame.find_smudge().as_ref()),
+            "X│..\n.│.#\n#│#.\n.│.#\n"
+        );
+        assert_eq!(frame.render(None), "#..\n..#\n##.\n..#\n");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let wide = "#".repeat(65);
+        let tall = "#\n".repeat(65);
+
+        assert_eq!(
+            wide.parse::<Frame>().err(),
+            Some(FrameError::TooLarge { rows: 1, cols: 65 })
+        );
+        assert_eq!(
+            tall.parse::<Frame>().err(),
+            Some(FrameError::TooLarge { rows: 65, cols: 1 })
+        );
+        assert!("#".repeat(64).parse::<Frame>().is_ok());
+        assert_eq!("#.\n#".parse::<Frame>().err(), Some(FrameError::Ragged(1)));
+        assert_eq!("".parse::<Frame>().err(), Some(FrameError::Empty));
+    }
 }
@@ -1,113 +1,171 @@
-use std::collections::{BTreeSet, HashMap};
+use std::collections::HashMap;
+use std::fmt;
 
-use itertools::Itertools;
+use aoc::Direction;
 
 const INPUT: &str = include_str!("../input/day14.txt");
 
-type StoneSet = BTreeSet<(isize, isize)>;
+const ROUND: u8 = b'O';
+const CUBE: u8 = b'#';
+const EMPTY: u8 = b'.';
 
-#[derive(Clone, Hash, PartialEq, Eq)]
+const SPIN_CYCLE: [Direction; 4] = [
+    Direction::North,
+    Direction::West,
+    Direction::South,
+    Direction::East,
+];
+
+/// Loads observed while spinning a platform until its state repeats.
+#[derive(Debug, PartialEq)]
+struct SpinHistory {
+    /// `loads[n]` is the load after `n` spin cycles.
+    loads: Vec<usize>,
+    /// First cycle count whose state is revisited.
+    start: usize,
+    period: usize,
+}
+
+impl SpinHistory {
+    fn load_after(&self, cycles: usize) -> usize {
+        match self.loads.get(cycles) {
+            Some(&load) => load,
+            None => self.loads[self.start + (cycles - self.start) % self.period],
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
 struct Platform {
-    persistent: StoneSet,
-    stones: StoneSet,
+    cells: Vec<u8>,
+    width: usize,
     height: usize,
 }
 
 impl Platform {
     fn new(input: impl AsRef<str>) -> Self {
-        let mut width: usize = 0;
-        let mut height: usize = 0;
-        let mut persistent = StoneSet::new();
-        let mut stones = StoneSet::new();
-
-        for (y, line) in input.as_ref().lines().enumerate() {
-            width = line.len();
-            for (x, ch) in line.chars().enumerate() {
-                match ch {
-                    '#' => {
-                        persistent.insert((x as isize + 1, y as isize + 1));
-                    }
-                    'O' => {
-                        stones.insert((x as isize + 1, y as isize + 1));
-                    }
-                    _ => {}
-                }
-            }
-
-            height += 1;
-        }
-
-        (0..height + 2).for_each(|y| {
-            persistent.insert((0, y as isize));
-            persistent.insert((width as isize + 1, y as isize));
-        });
-
-        (0..width + 2).for_each(|x| {
-            persistent.insert((x as isize, 0));
-            persistent.insert((x as isize, height as isize + 1));
-        });
+        let lines = input.as_ref().lines().collect::<Vec<_>>();
+        let width = lines.first().map_or(0, |line| line.len());
 
         Self {
-            persistent,
-            stones,
-            height: height + 2,
+            cells: lines.iter().flat_map(|line| line.bytes()).collect(),
+            width,
+            height: lines.len(),
         }
     }
-    fn count(&self) -> usize {
-        self.stones
+
+    /// Total load on the `edge` support beams: each round rock counts its
+    /// distance from the opposite edge, with rocks on that edge counting one.
+    fn load(&self, edge: Direction) -> usize {
+        self.cells
             .iter()
-            .map(|pos| self.height - 1 - pos.1 as usize)
+            .enumerate()
+            .filter(|(_, &c)| c == ROUND)
+            .map(|(i, _)| {
+                let (row, col) = (i / self.width, i % self.width);
+
+                match edge {
+                    Direction::North => self.height - row,
+                    Direction::South => row + 1,
+                    Direction::West => self.width - col,
+                    Direction::East => col + 1,
+                }
+            })
             .sum()
     }
 
-    fn tilt(&mut self) {
-        let mut new_stones = StoneSet::new();
-        self.stones
-            .iter()
-            .sorted_by_key(|pos| pos.1)
-            .for_each(|pos| {
-                let mut pos = *pos;
-
-                while !new_stones.contains(&(pos.0, pos.1 - 1))
-                    && !self.persistent.contains(&(pos.0, pos.1 - 1))
-                {
-                    pos.1 -= 1;
+    /// Returns `(lanes, lane length, lane stride, step stride)` for sliding rocks along
+    /// `direction`, so that cell `pos` of lane `lane` is at `lane * lane_stride + pos * step`.
+    fn lanes(&self, direction: Direction) -> (usize, usize, usize, usize) {
+        match direction {
+            Direction::North | Direction::South => (self.width, self.height, 1, self.width),
+            Direction::East | Direction::West => (self.height, self.width, self.width, 1),
+        }
+    }
+
+    fn tilt(&mut self, direction: Direction) {
+        let (lanes, len, lane_stride, step) = self.lanes(direction);
+        let towards_start = matches!(direction, Direction::North | Direction::West);
+
+        for lane in 0..lanes {
+            let base = lane * lane_stride;
+            let mut start = 0;
+            let mut rocks = 0;
+
+            for pos in 0..=len {
+                let cell = (pos < len).then(|| self.cells[base + pos * step]);
+
+                match cell {
+                    Some(ROUND) => rocks += 1,
+                    Some(CUBE) | None => {
+                        let filled = if towards_start {
+                            start..start + rocks
+                        } else {
+                            pos - rocks..pos
+                        };
+
+                        for i in start..pos {
+                            self.cells[base + i * step] =
+                                if filled.contains(&i) { ROUND } else { EMPTY };
+                        }
+
+                        start = pos + 1;
+                        rocks = 0;
+                    }
+                    _ => {}
                 }
+            }
+        }
+    }
+
+    /// Packs the round rock positions into a bitset; cube rocks never move, so
+    /// this identifies the platform state.
+    fn fingerprint(&self) -> Vec<u64> {
+        let mut bits = vec![0; self.cells.len().div_ceil(64)];
 
-                new_stones.insert(pos);
-            });
+        for (i, _) in self.cells.iter().enumerate().filter(|(_, &c)| c == ROUND) {
+            bits[i / 64] |= 1 << (i % 64);
+        }
 
-        self.stones = new_stones;
+        bits
     }
 
-    fn cycle(&mut self) {
-        self.tilt();
+    fn spin_history(&self, sequence: &[Direction], edge: Direction) -> SpinHistory {
+        let mut platform = self.clone();
+        let mut loads = vec![platform.load(edge)];
+        let mut visited = HashMap::from([(platform.fingerprint(), 0)]);
 
-        self.rotate(1);
-        self.tilt();
-        self.rotate(3);
+        loop {
+            platform.spin(sequence);
+            let cycles = loads.len();
 
-        self.rotate(2);
-        self.tilt();
-        self.rotate(2);
+            if let Some(&start) = visited.get(&platform.fingerprint()) {
+                return SpinHistory {
+                    loads,
+                    start,
+                    period: cycles - start,
+                };
+            }
 
-        self.rotate(3);
-        self.tilt();
-        self.rotate(1);
+            visited.insert(platform.fingerprint(), cycles);
+            loads.push(platform.load(edge));
+        }
     }
 
-    fn rotate(&mut self, times: usize) {
-        self.stones = Self::rotate_inner(&self.stones, times);
-        self.persistent = Self::rotate_inner(&self.persistent, times);
+    fn spin(&mut self, sequence: &[Direction]) {
+        for &direction in sequence {
+            self.tilt(direction);
+        }
     }
+}
 
-    fn rotate_inner(points: &StoneSet, times: usize) -> StoneSet {
-        match times % 4 {
-            3 => points.iter().map(|&(x, y)| (y, -x)).collect(),
-            2 => points.iter().map(|&(x, y)| (-x, -y)).collect(),
-            1 => points.iter().map(|&(x, y)| (-y, x)).collect(),
-            _ => points.clone(),
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in self.cells.chunks(self.width) {
+            writeln!(f, "{}", String::from_utf8_lossy(line))?;
         }
+
+        Ok(())
     }
 }
 
@@ -118,28 +176,14 @@ fn main() {
 
 fn part1(input: impl AsRef<str>) -> usize {
     let mut platform = Platform::new(input);
-    platform.tilt();
-    platform.count()
+    platform.tilt(Direction::North);
+    platform.load(Direction::North)
 }
 
 fn part2(input: impl AsRef<str>) -> usize {
-    let mut platform = Platform::new(input.as_ref());
-    let mut cache = vec![];
-    let mut visited = HashMap::new();
-
-    let index = loop {
-        platform.cycle();
-
-        if let Some(&index) = visited.get(&platform) {
-            break index;
-        } else {
-            visited.insert(platform.clone(), cache.len());
-            cache.push(platform.count());
-        }
-    };
-
-    let index = (1_000_000_000 - cache.len() - 1) % (cache.len() - index) + index;
-    cache[index]
+    Platform::new(input)
+        .spin_history(&SPIN_CYCLE, Direction::North)
+        .load_after(1_000_000_000)
 }
 
 #[cfg(test)]
@@ -166,4 +210,93 @@ O.#..O.#.#
     fn test_part2() {
         assert_eq!(part2(INPUT), 64);
     }
+
+    #[test]
+    fn test_spin_cycle() {
+        let expected = [
+            ".....#....
+....#...O#
+...OO##...
+.OO#......
+.....OOO#.
+.O#...O#.#
+....O#....
+......OOOO
+#...O###..
+#..OO#....
+",
+            ".....#....
+....#...O#
+.....##...
+..O#......
+.....OOO#.
+.O#...O#.#
+....O#...O
+.......OOO
+#..OO###..
+#.OOO#...O
+",
+            ".....#....
+....#...O#
+.....##...
+..O#......
+.....OOO#.
+.O#...O#.#
+....O#...O
+.......OOO
+#...O###.O
+#.OOO#...O
+",
+        ];
+        let mut platform = Platform::new(INPUT);
+
+        for state in expected {
+            platform.spin(&SPIN_CYCLE);
+            assert_eq!(platform.to_string(), state);
+        }
+    }
+
+    #[test]
+    fn test_custom_spin_and_edges() {
+        let mut platform = Platform::new("O.#.O\n.O..O");
+
+        assert_eq!(platform.load(Direction::North), 6);
+        assert_eq!(platform.load(Direction::South), 6);
+        assert_eq!(platform.load(Direction::West), 11);
+        assert_eq!(platform.load(Direction::East), 13);
+
+        platform.spin(&[Direction::East, Direction::South]);
+        assert_eq!(platform.to_string(), "..#.O\n.O.OO\n");
+        assert_eq!(platform.load(Direction::South), 7);
+
+        let history = Platform::new(INPUT).spin_history(&[Direction::North], Direction::North);
+        assert_eq!((history.start, history.period), (1, 1));
+        assert_eq!(history.load_after(1_000_000_000), 136);
+    }
+
+    #[test]
+    fn test_tilt() {
+        let tilted = |direction| {
+            let mut platform = Platform::new("O.#.O\n.O..O");
+            platform.tilt(direction);
+            platform.to_string()
+        };
+
+        assert_eq!(tilted(Direction::North), "OO#.O\n....O\n");
+        assert_eq!(tilted(Direction::South), "..#.O\nOO..O\n");
+        assert_eq!(tilted(Direction::West), "O.#O.\nOO...\n");
+        assert_eq!(tilted(Direction::East), ".O#.O\n...OO\n");
+    }
+
+    #[test]
+    fn test_spin_history() {
+        let history = Platform::new(INPUT).spin_history(&SPIN_CYCLE, Direction::North);
+
+        assert_eq!(history.start, 3);
+        assert_eq!(history.period, 7);
+        assert_eq!(history.loads[..4], [104, 87, 69, 69]);
+        assert_eq!(history.load_after(3), 69);
+        assert_eq!(history.load_after(3 + 7 * 1000), 69);
+        assert_eq!(history.load_after(1_000_000_000), 64);
+    }
 }
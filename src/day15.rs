@@ -1,9 +1,136 @@
-use std::iter::repeat;
+use std::fmt;
+use std::iter::repeat_n;
+use std::num::ParseIntError;
+use std::str::FromStr;
 
+use derive_more::Display;
 use indexmap::IndexMap;
 
 const INPUT: &str = include_str!("../input/day15.txt");
 
+#[derive(Debug, Display, PartialEq)]
+enum StepError {
+    #[display("missing operation in step {_0:?}")]
+    MissingOperation(String),
+    #[display("invalid focal length: {_0}")]
+    InvalidFocalLength(ParseIntError),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Remove(String),
+    Insert(String, usize),
+}
+
+impl Step {
+    fn label(&self) -> &str {
+        match self {
+            Self::Remove(label) | Self::Insert(label, _) => label,
+        }
+    }
+}
+
+impl FromStr for Step {
+    type Err = StepError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(label) = s.strip_suffix('-') {
+            return Ok(Self::Remove(label.to_string()));
+        }
+
+        let (label, focal) = s
+            .split_once('=')
+            .ok_or_else(|| StepError::MissingOperation(s.to_string()))?;
+        let focal = focal.parse().map_err(StepError::InvalidFocalLength)?;
+
+        Ok(Self::Insert(label.to_string(), focal))
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Remove(label) => write!(f, "{label}-"),
+            Self::Insert(label, focal) => write!(f, "{label}={focal}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct LensBoxes {
+    boxes: Vec<IndexMap<String, usize>>,
+}
+
+impl Default for LensBoxes {
+    fn default() -> Self {
+        Self {
+            boxes: Vec::from_iter(repeat_n(IndexMap::new(), 256)),
+        }
+    }
+}
+
+impl LensBoxes {
+    fn apply(&mut self, step: Step) {
+        let lenses = &mut self.boxes[hash(step.label())];
+
+        match step {
+            Step::Remove(label) => {
+                lenses.shift_remove(&label);
+            }
+            Step::Insert(label, focal) => {
+                lenses.insert(label, focal);
+            }
+        }
+    }
+
+    fn focusing_power(&self) -> usize {
+        self.boxes
+            .iter()
+            .enumerate()
+            .flat_map(|(box_id, lenses)| {
+                lenses
+                    .values()
+                    .enumerate()
+                    .map(move |(idx, &fp)| (box_id + 1) * (idx + 1) * fp)
+            })
+            .sum()
+    }
+}
+
+impl fmt::Display for LensBoxes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (box_id, lenses) in self.boxes.iter().enumerate() {
+            if lenses.is_empty() {
+                continue;
+            }
+
+            write!(f, "Box {box_id}:")?;
+            for (label, focal) in lenses {
+                write!(f, " [{label} {focal}]")?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Replays `steps`, printing the boxes after each one like the puzzle's walkthrough.
+#[allow(dead_code)]
+fn trace(steps: &str) -> Result<String, StepError> {
+    let mut boxes = LensBoxes::default();
+    let mut out = String::new();
+
+    for step in steps.split(',') {
+        let step = step.parse::<Step>()?;
+        out += &format!("After \"{step}\":\n");
+        boxes.apply(step);
+        out += &format!("{boxes}\n");
+    }
+
+    Ok(out)
+}
+
 fn main() {
     println!("Part 1: {}", part1(INPUT));
     println!("Part 2: {}", part2(INPUT));
@@ -23,37 +150,12 @@ fn part1(input: &str) -> usize {
 fn part2(input: &str) -> usize {
     input
         .split(',')
-        .map(|s| {
-            let (label, value) = s.split_once(|c| matches!(c, '-' | '=')).unwrap();
-            let operation = s.chars().find(|c| matches!(*c, '-' | '=')).unwrap();
-            let op = (operation == '=').then(|| value.parse::<usize>().unwrap());
-
-            (label, hash(label), op)
+        .map(|s| s.parse::<Step>().unwrap())
+        .fold(LensBoxes::default(), |mut boxes, step| {
+            boxes.apply(step);
+            boxes
         })
-        .fold(
-            &mut Vec::from_iter(repeat(IndexMap::new()).take(256)),
-            |boxes, action| {
-                match action {
-                    (label, box_id, None) => {
-                        boxes[box_id].shift_remove(label);
-                    }
-                    (label, box_id, Some(value)) => {
-                        *boxes[box_id].entry(label).or_insert(value) = value;
-                    }
-                }
-
-                boxes
-            },
-        )
-        .iter()
-        .enumerate()
-        .flat_map(|(box_id, lenses)| {
-            lenses
-                .values()
-                .enumerate()
-                .map(move |(idx, &fp)| (box_id + 1) * (idx + 1) * fp)
-        })
-        .sum()
+        .focusing_power()
 }
 
 #[cfg(test)]
@@ -71,4 +173,51 @@ mod tests {
     fn test_part2() {
         assert_eq!(part2(INPUT), 145);
     }
+
+    #[test]
+    fn test_trace() {
+        assert_eq!(
+            trace("rn=1,cm-,qp=3,cm=2").unwrap(),
+            "After \"rn=1\":
+Box 0: [rn 1]
+
+After \"cm-\":
+Box 0: [rn 1]
+
+After \"qp=3\":
+Box 0: [rn 1]
+Box 1: [qp 3]
+
+After \"cm=2\":
+Box 0: [rn 1] [cm 2]
+Box 1: [qp 3]
+
+"
+        );
+
+        let mut boxes = LensBoxes::default();
+        INPUT
+            .split(',')
+            .for_each(|step| boxes.apply(step.parse().unwrap()));
+        assert_eq!(
+            boxes.to_string(),
+            "Box 0: [rn 1] [cm 2]\nBox 3: [ot 7] [ab 5] [pc 6]\n"
+        );
+        assert_eq!(boxes.focusing_power(), 145);
+    }
+
+    #[test]
+    fn test_parse_step() {
+        assert_eq!("cm-".parse(), Ok(Step::Remove("cm".to_string())));
+        assert_eq!("qp=3".parse(), Ok(Step::Insert("qp".to_string(), 3)));
+        assert_eq!(
+            "qp".parse::<Step>(),
+            Err(StepError::MissingOperation("qp".to_string()))
+        );
+        assert!(matches!(
+            "qp=x".parse::<Step>(),
+            Err(StepError::InvalidFocalLength(_))
+        ));
+        assert_eq!(Step::Insert("qp".to_string(), 3).to_string(), "qp=3");
+    }
 }
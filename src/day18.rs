@@ -1,5 +1,3 @@
-use std::isize;
-
 const INPUT: &str = include_str!("../input/day18.txt");
 
 fn main() {
@@ -36,14 +36,14 @@ impl<'a> Module<'a> {
     }
 }
 
-fn parse_modules(input: &str) -> Modules {
+fn parse_modules(input: &str) -> Modules<'_> {
     let mut modules = Modules::new();
 
     for line in input.lines() {
         let (id, output) = line.split_once(" -> ").unwrap();
         let outputs = output.split(", ").collect();
         let mode = id.chars().next().unwrap();
-        let id = id.trim_start_matches(|c| c == '%' || c == '&');
+        let id = id.trim_start_matches(['%', '&']);
 
         let module = match mode {
             '%' => Module::FlipFlop {
@@ -211,7 +211,7 @@ fn push_button(modules: &mut Modules, counts: &mut [usize; 2]) {
 fn find_deps<'a>(id: &'a str, modules: &'a Modules) -> Vec<&'a str> {
     modules
         .iter()
-        .filter(|(_, module)| module.outputs().iter().any(|output_id| *output_id == id))
+        .filter(|(_, module)| module.outputs().contains(&id))
         .map(|(id, _)| *id)
         .collect()
 }
@@ -1,4 +1,4 @@
-use std::{collections::HashSet, str::FromStr};
+use std::{collections::HashSet, ops::RangeBounds, str::FromStr};
 
 use itertools::Itertools;
 
@@ -21,6 +21,13 @@ const NEIGHBORS: [(isize, isize); 8] = [
     (1, 1),
 ];
 
+#[derive(Debug, PartialEq)]
+struct Symbol {
+    symbol: char,
+    position: (usize, usize),
+    parts: Vec<u32>,
+}
+
 impl FromStr for Schematic {
     type Err = &'static str;
 
@@ -59,32 +66,63 @@ impl Schematic {
             .collect()
     }
 
-    fn num_at(&self, x: usize, y: usize) -> u32 {
-        let mut digits = (0..=x)
+    fn part_at(&self, x: usize, y: usize) -> ((usize, usize), u32) {
+        let start = (0..=x)
             .rev()
-            .map(|x| *self.get(x, y).unwrap())
-            .take_while(char::is_ascii_digit)
-            .map(|c| c.to_digit(10).unwrap())
-            .collect::<Vec<_>>();
-
-        digits.reverse();
-
-        digits.extend(
-            (x + 1..self.width)
-                .map(|x| *self.get(x, y).unwrap())
-                .take_while(char::is_ascii_digit)
-                .map(|c| c.to_digit(10).unwrap())
-                .collect::<Vec<_>>(),
-        );
+            .take_while(|&x| self.get(x, y).is_some_and(char::is_ascii_digit))
+            .last()
+            .unwrap_or(x);
 
-        digits
-            .iter()
-            .rev()
-            .fold((1, 0), |(mul, sum), &d| (mul * 10, sum + d * mul))
-            .1
+        let value = (start..self.width)
+            .map_while(|x| self.get(x, y)?.to_digit(10))
+            .fold(0, |v, d| v * 10 + d);
+
+        ((start, y), value)
+    }
+
+    fn symbols(
+        &self,
+        is_symbol: impl Fn(char) -> bool,
+        target: Option<char>,
+        arity: impl RangeBounds<usize>,
+    ) -> Vec<Symbol> {
+        let mut symbols = Vec::new();
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let c = *self.get(x, y).unwrap();
+                if !is_symbol(c) || target.is_some_and(|t| t != c) {
+                    continue;
+                }
+
+                let parts = self
+                    .get_neighbors(x, y)
+                    .iter()
+                    .filter(|(c, _)| c.is_ascii_digit())
+                    .map(|&(_, (nx, ny))| self.part_at(nx, ny))
+                    .unique_by(|&(start, _)| start)
+                    .sorted_by_key(|&((x, y), _)| (y, x))
+                    .map(|(_, value)| value)
+                    .collect::<Vec<_>>();
+
+                if arity.contains(&parts.len()) {
+                    symbols.push(Symbol {
+                        symbol: c,
+                        position: (x, y),
+                        parts,
+                    });
+                }
+            }
+        }
+
+        symbols
     }
 }
 
+fn is_symbol(c: char) -> bool {
+    c != '.' && !c.is_ascii_digit()
+}
+
 fn main() {
     let s = Schematic::from_str(INPUT).unwrap();
     println!("Part 1: {}", part1(&s));
@@ -116,7 +154,7 @@ fn part1(s: &Schematic) -> i32 {
             if positions
                 .iter()
                 .flat_map(|p| s.get_neighbors(p.0, p.1))
-                .any(|(c, _)| c != &'.' && !c.is_ascii_digit())
+                .any(|(&c, _)| is_symbol(c))
             {
                 total += num as i32;
             }
@@ -129,42 +167,17 @@ fn part1(s: &Schematic) -> i32 {
 }
 
 fn part2(s: &Schematic) -> u32 {
-    let mut total = 0;
-    for x in 0..s.width {
-        for y in 0..s.height {
-            if s.get(x, y) != Some(&'*') {
-                continue;
-            }
-
-            let nums = s
-                .get_neighbors(x, y)
-                .iter()
-                .filter_map(|(c, pos)| {
-                    if !c.is_ascii_digit() {
-                        return None;
-                    }
-
-                    Some(s.num_at(pos.0, pos.1))
-                })
-                .unique()
-                .collect::<Vec<_>>();
-
-            if nums.len() != 2 {
-                continue;
-            }
-
-            total += nums.iter().product::<u32>();
-        }
-    }
-
-    total
+    s.symbols(is_symbol, Some('*'), 2..=2)
+        .iter()
+        .map(|symbol| symbol.parts.iter().product::<u32>())
+        .sum()
 }
 
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
 
-    use crate::{part1, part2, Schematic};
+    use crate::{is_symbol, part1, part2, Schematic, Symbol};
 
     const INPUT: &str = "467..114..
 ...*......
@@ -186,4 +199,43 @@ mod tests {
     fn test_part2() {
         assert_eq!(part2(&Schematic::from_str(INPUT).unwrap()), 467835);
     }
+
+    #[test]
+    fn test_symbols() {
+        let s = Schematic::from_str(INPUT).unwrap();
+
+        assert_eq!(
+            s.symbols(is_symbol, Some('*'), 2..=2),
+            vec![
+                Symbol {
+                    symbol: '*',
+                    position: (3, 1),
+                    parts: vec![467, 35],
+                },
+                Symbol {
+                    symbol: '*',
+                    position: (5, 8),
+                    parts: vec![755, 598],
+                },
+            ]
+        );
+        assert_eq!(s.symbols(is_symbol, Some('*'), 1..=1).len(), 1);
+        assert_eq!(s.symbols(is_symbol, None, 1..).len(), 6);
+        assert!(s.symbols(is_symbol, Some('#'), 3..).is_empty());
+        assert_eq!(s.symbols(|c| c == '#', None, ..).len(), 1);
+    }
+
+    #[test]
+    fn test_symbols_repeated_part() {
+        let s = Schematic::from_str("12.....\n..#12..\n12...*.\n.....12").unwrap();
+
+        assert_eq!(
+            s.symbols(is_symbol, Some('#'), 3..)[0].parts,
+            vec![12, 12, 12]
+        );
+        assert_eq!(
+            s.symbols(is_symbol, Some('*'), 2..=2)[0].parts,
+            vec![12, 12]
+        );
+    }
 }
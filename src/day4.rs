@@ -1,14 +1,57 @@
 use std::{collections::HashSet, str::FromStr};
 
 use aoc::parse_nums;
+use derive_more::Display;
+use rayon::prelude::*;
 
 const INPUT: &str = include_str!("../input/day4.txt");
 
+#[derive(Debug)]
+enum Numbers {
+    Mask(u128),
+    Set(HashSet<u32>),
+}
+
+impl FromIterator<u32> for Numbers {
+    fn from_iter<I: IntoIterator<Item = u32>>(iter: I) -> Self {
+        let nums = iter.into_iter().collect::<Vec<_>>();
+
+        if nums.iter().all(|&n| n < u128::BITS) {
+            Self::Mask(nums.iter().fold(0, |mask, &n| mask | 1 << n))
+        } else {
+            Self::Set(nums.into_iter().collect())
+        }
+    }
+}
+
+impl Numbers {
+    fn contains(&self, n: u32) -> bool {
+        match self {
+            Self::Mask(mask) => n < u128::BITS && mask & 1 << n != 0,
+            Self::Set(set) => set.contains(&n),
+        }
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = u32> + '_> {
+        match self {
+            Self::Mask(mask) => Box::new((0..u128::BITS).filter(move |&n| mask & 1 << n != 0)),
+            Self::Set(set) => Box::new(set.iter().copied()),
+        }
+    }
+
+    fn intersection_count(&self, other: &Self) -> usize {
+        match (self, other) {
+            (Self::Mask(a), Self::Mask(b)) => (a & b).count_ones() as usize,
+            _ => self.iter().filter(|&n| other.contains(n)).count(),
+        }
+    }
+}
+
 #[derive(Debug)]
 struct Card {
     id: usize,
-    winning: HashSet<u32>,
-    numbers: HashSet<u32>,
+    winning: Numbers,
+    numbers: Numbers,
 }
 
 impl FromStr for Card {
@@ -30,7 +73,106 @@ impl FromStr for Card {
 
 impl Card {
     fn get_winning_count(&self) -> usize {
-        self.numbers.intersection(&self.winning).count()
+        self.numbers.intersection_count(&self.winning)
+    }
+}
+
+#[derive(Debug, Display, PartialEq)]
+enum GameError {
+    #[display("invalid card")]
+    InvalidCard,
+    #[display("duplicate card {_0}")]
+    DuplicateCard(usize),
+    #[display("copies of card {_0} overflow")]
+    Overflow(usize),
+}
+
+#[derive(Debug, PartialEq)]
+struct Spawn {
+    from: usize,
+    to: usize,
+    copies: u64,
+}
+
+#[derive(Debug)]
+struct Outcome {
+    copies: Vec<(usize, u64)>,
+}
+
+impl Outcome {
+    fn total(&self) -> u128 {
+        self.copies.iter().map(|&(_, count)| count as u128).sum()
+    }
+}
+
+struct ScratchcardGame {
+    cards: Vec<Card>,
+}
+
+impl FromStr for ScratchcardGame {
+    type Err = GameError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut cards = s
+            .par_lines()
+            .map(str::parse)
+            .collect::<Result<Vec<Card>, _>>()
+            .map_err(|()| GameError::InvalidCard)?;
+        cards.sort_by_key(|card| card.id);
+
+        if let Some(pair) = cards.windows(2).find(|pair| pair[0].id == pair[1].id) {
+            return Err(GameError::DuplicateCard(pair[0].id));
+        }
+
+        Ok(Self { cards })
+    }
+}
+
+impl ScratchcardGame {
+    fn play(&self) -> Result<Outcome, GameError> {
+        self.run(|_| {})
+    }
+
+    #[allow(dead_code)]
+    fn play_traced(&self) -> Result<(Outcome, Vec<Spawn>), GameError> {
+        let mut trace = Vec::new();
+        let outcome = self.run(|spawn| trace.push(spawn))?;
+
+        Ok((outcome, trace))
+    }
+
+    /// A card with `n` matches wins copies of cards `id + 1 ..= id + n`; IDs
+    /// missing from the game are skipped rather than replaced by later cards.
+    fn run(&self, mut on_spawn: impl FnMut(Spawn)) -> Result<Outcome, GameError> {
+        let winning_counts = self
+            .cards
+            .par_iter()
+            .map(Card::get_winning_count)
+            .collect::<Vec<_>>();
+
+        let mut copies = vec![1u64; self.cards.len()];
+
+        for (index, winning_count) in winning_counts.into_iter().enumerate() {
+            let id = self.cards[index].id;
+            let count = copies[index];
+            let won = (index + 1..self.cards.len())
+                .take_while(|&next| self.cards[next].id <= id + winning_count);
+
+            for next in won {
+                copies[next] = copies[next]
+                    .checked_add(count)
+                    .ok_or(GameError::Overflow(self.cards[next].id))?;
+                on_spawn(Spawn {
+                    from: id,
+                    to: self.cards[next].id,
+                    copies: count,
+                });
+            }
+        }
+
+        Ok(Outcome {
+            copies: self.cards.iter().map(|card| card.id).zip(copies).collect(),
+        })
     }
 }
 
@@ -39,37 +181,34 @@ fn main() {
     println!("Part 2: {}", part2(INPUT));
 }
 
-fn part1(input: &str) -> u32 {
+fn part1(input: &str) -> u128 {
     input
-        .lines()
+        .par_lines()
         .filter_map(|l| l.parse::<Card>().ok())
         .map(|card| card.get_winning_count())
         .filter(|&winning_count| winning_count > 0)
-        .map(|winning_count| 1 << (winning_count - 1))
+        .map(|winning_count| {
+            1u128
+                .checked_shl(winning_count as u32 - 1)
+                .expect("card points overflow u128")
+        })
         .sum()
 }
 
-fn part2(input: &str) -> u32 {
-    let cards_count = input.lines().count();
-    let mut results = vec![0; cards_count];
-
-    for line in input.lines() {
-        let card = Card::from_str(line).unwrap();
-        let winning_count = card.get_winning_count();
-
-        results[card.id - 1] += 1;
-
-        if winning_count > 0 {
-            let count = results[card.id - 1];
-            (0..winning_count).for_each(|id| results[card.id + id] += count);
-        }
-    }
-
-    results.iter().sum()
+fn part2(input: &str) -> u128 {
+    ScratchcardGame::from_str(input)
+        .unwrap()
+        .play()
+        .unwrap()
+        .total()
 }
 
 #[cfg(test)]
 mod tests {
+    use std::str::FromStr;
+
+    use itertools::Itertools;
+
     const INPUT: &str = "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
 Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
 Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
@@ -87,4 +226,145 @@ Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
     fn test_part2() {
         assert_eq!(super::part2(INPUT), 30);
     }
+
+    #[test]
+    fn test_play() {
+        let game = super::ScratchcardGame::from_str(INPUT).unwrap();
+        let (outcome, trace) = game.play_traced().unwrap();
+
+        assert_eq!(
+            outcome.copies,
+            vec![(1, 1), (2, 2), (3, 4), (4, 8), (5, 14), (6, 1)]
+        );
+        assert_eq!(outcome.total(), 30);
+        assert_eq!(trace.len(), 9);
+        assert_eq!(
+            trace[5],
+            super::Spawn {
+                from: 2,
+                to: 4,
+                copies: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_play_bounds_and_gaps() {
+        // card 3 wins 4..=6 and card 10 wins 11..=13, none of which exist
+        let input = "Card 10: 1 2 3 | 1 2 3
+Card 3: 4 5 6 | 4 5 6
+Card 7: 1 | 2";
+        let outcome = super::ScratchcardGame::from_str(input)
+            .unwrap()
+            .play()
+            .unwrap();
+
+        assert_eq!(outcome.copies, vec![(3, 1), (7, 1), (10, 1)]);
+        assert_eq!(outcome.total(), 3);
+
+        // card 1 wins 2..=3; the missing card 3 is skipped, not replaced by card 4
+        let input = "Card 1: 1 2 | 1 2
+Card 2: 5 | 6
+Card 4: 7 | 8";
+        let outcome = super::ScratchcardGame::from_str(input)
+            .unwrap()
+            .play()
+            .unwrap();
+
+        assert_eq!(outcome.copies, vec![(1, 1), (2, 2), (4, 1)]);
+    }
+
+    #[test]
+    fn test_play_overflow() {
+        let input = (1..=100).map(|id| format!("Card {id}: 1 | 1")).join("\n");
+        let game = super::ScratchcardGame::from_str(&input).unwrap();
+
+        assert_eq!(game.play().unwrap().total(), 100 * 101 / 2);
+
+        let input = (1..=200)
+            .map(|id| format!("Card {id}: 1 2 3 | 1 2 3"))
+            .join("\n");
+        let game = super::ScratchcardGame::from_str(&input).unwrap();
+
+        assert!(matches!(game.play(), Err(super::GameError::Overflow(_))));
+    }
+
+    #[test]
+    fn test_duplicate_cards() {
+        let input = "Card 1: 1 | 1\nCard 2: 2 | 3\nCard 1: 4 | 4";
+
+        assert_eq!(
+            super::ScratchcardGame::from_str(input).err(),
+            Some(super::GameError::DuplicateCard(1))
+        );
+    }
+
+    #[test]
+    fn test_large_numbers() {
+        let card = super::Card::from_str("Card 1: 5 200 1000 | 1000 5 7 200").unwrap();
+
+        assert!(matches!(card.winning, super::Numbers::Set(_)));
+        assert_eq!(card.get_winning_count(), 3);
+
+        let numbers = (1000..1040).join(" ");
+        let input = format!("Card 1: {numbers} | {numbers}");
+        assert_eq!(super::part1(&input), 1 << 39);
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_matching() {
+        use std::{collections::HashSet, time::Instant};
+
+        let input = (1..=500_000)
+            .map(|id| {
+                let winning = (0..10).map(|n| (id * 7 + n * 13) % 100).join(" ");
+                let numbers = (0..25).map(|n| (id * 11 + n * 17) % 100).join(" ");
+                format!("Card {id}: {winning} | {numbers}")
+            })
+            .join("\n");
+
+        let now = Instant::now();
+        let game = super::ScratchcardGame::from_str(&input).unwrap();
+        let parsed = now.elapsed();
+
+        let now = Instant::now();
+        let bitset = game
+            .cards
+            .iter()
+            .map(|card| card.get_winning_count())
+            .sum::<usize>();
+        let bitset_elapsed = now.elapsed();
+
+        let sets = game
+            .cards
+            .iter()
+            .map(|card| {
+                let winning = card.winning.iter().collect::<HashSet<_>>();
+                let numbers = card.numbers.iter().collect::<HashSet<_>>();
+                (winning, numbers)
+            })
+            .collect::<Vec<_>>();
+
+        let now = Instant::now();
+        let hashset = sets
+            .iter()
+            .map(|(winning, numbers)| numbers.intersection(winning).count())
+            .sum::<usize>();
+        let hashset_elapsed = now.elapsed();
+
+        let now = Instant::now();
+        let outcome = game.play();
+        let play_elapsed = now.elapsed();
+
+        assert_eq!(bitset, hashset);
+        println!("parse (parallel): {parsed:?}");
+        println!("match (bitset):   {bitset_elapsed:?}");
+        println!("match (HashSet):  {hashset_elapsed:?}");
+        println!("play:             {play_elapsed:?}");
+        match outcome {
+            Ok(outcome) => println!("total:            {}", outcome.total()),
+            Err(err) => println!("total:            {err}"),
+        }
+    }
 }
@@ -1,160 +1,162 @@
 use std::cmp::Ordering;
-use std::collections::HashMap;
-use std::hash::{Hash, Hasher};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
 use std::num::ParseIntError;
 use std::str::FromStr;
 
+use derive_more::Display;
 use itertools::Itertools;
 
 const INPUT: &str = include_str!("../input/day7.txt");
 
-#[derive(PartialEq, Eq)]
-struct Part1;
-
-#[derive(PartialEq, Eq)]
-struct Part2;
-
-#[derive(Debug, Clone)]
-struct Card(char, Option<u32>);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ruleset {
+    order: Vec<char>,
+    wild: Vec<char>,
+    hand_size: usize,
+}
 
-impl Hash for Card {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.0.hash(state)
+impl Default for Ruleset {
+    fn default() -> Self {
+        Self::new("23456789TJQKA", "")
     }
 }
 
-impl PartialEq for Card {
-    fn eq(&self, other: &Self) -> bool {
-        self.0 == other.0
+impl Ruleset {
+    pub fn new(order: &str, wild: &str) -> Self {
+        Self {
+            order: order.chars().collect(),
+            wild: wild.chars().collect(),
+            hand_size: 5,
+        }
     }
-}
 
-impl Eq for Card {}
+    pub fn with_hand_size(self, hand_size: usize) -> Self {
+        Self { hand_size, ..self }
+    }
 
-impl Card {
-    fn new(c: char) -> Self {
-        Self(c, None)
+    pub fn jokers() -> Self {
+        Self::new("J23456789TQKA", "J")
     }
 
-    fn joker_value(&self) -> u32 {
-        self.1.unwrap_or_else(|| self.value())
+    fn value(&self, card: char) -> Option<u32> {
+        self.order
+            .iter()
+            .position(|&c| c == card)
+            .map(|value| value as u32)
     }
 
-    fn value(&self) -> u32 {
-        match self.0 {
-            'A' => 14,
-            'K' => 13,
-            'Q' => 12,
-            'J' => 11,
-            'T' => 10,
-            c => c.to_digit(10).unwrap(),
-        }
+    fn is_wild(&self, card: char) -> bool {
+        self.wild.contains(&card)
     }
 }
 
-impl PartialOrd for Card {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HandType {
+    HighCard,
+    OnePair,
+    TwoPair,
+    ThreeOfAKind,
+    FullHouse,
+    FourOfAKind,
+    FiveOfAKind,
 }
 
-impl Ord for Card {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.value().cmp(&other.value())
+impl HandType {
+    fn classify(cards: &[char], ruleset: &Ruleset) -> Self {
+        let wild = cards.iter().filter(|&&c| ruleset.is_wild(c)).count();
+        let mut signature = cards
+            .iter()
+            .filter(|&&c| !ruleset.is_wild(c))
+            .counts()
+            .into_values()
+            .sorted_unstable_by(|a, b| b.cmp(a))
+            .collect::<Vec<_>>();
+
+        match signature.first_mut() {
+            Some(largest) => *largest += wild,
+            None => signature.push(wild),
+        }
+
+        match signature.as_slice() {
+            [n, ..] if *n >= 5 => Self::FiveOfAKind,
+            [4, ..] => Self::FourOfAKind,
+            [3, n, ..] if *n >= 2 => Self::FullHouse,
+            [3, ..] => Self::ThreeOfAKind,
+            [2, 2, ..] => Self::TwoPair,
+            [2, ..] => Self::OnePair,
+            _ => Self::HighCard,
+        }
     }
 }
 
+#[derive(Debug, Display, PartialEq, Eq)]
+pub enum ParseHandError {
+    #[display("missing bid")]
+    MissingBid,
+    #[display("invalid bid: {_0}")]
+    InvalidBid(ParseIntError),
+    #[display("expected {expected} cards, found {found}")]
+    InvalidLength { expected: usize, found: usize },
+    #[display("invalid card {card:?} at position {position}")]
+    InvalidCard { card: char, position: usize },
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
-struct Hand {
-    cards: [Card; 5],
+pub struct Hand {
+    cards: Vec<char>,
+    hand_type: HandType,
+    values: Vec<u32>,
     bid: u64,
 }
 
 impl Hand {
-    fn hashmap(&self) -> HashMap<&Card, u64> {
-        self.cards.iter().fold(HashMap::new(), |mut acc, card| {
-            *acc.entry(card).or_insert(0) += 1;
-            acc
-        })
-    }
-
-    fn is32(&self) -> bool {
-        let hm = self.hashmap();
-        hm.values().contains(&3) && hm.values().contains(&2)
-    }
-
-    fn is5(&self) -> bool {
-        self.cards.iter().all_equal()
-    }
-
-    fn is4(&self) -> bool {
-        self.hashmap().values().contains(&4)
-    }
-
-    fn is3(&self) -> bool {
-        !self.is32() && self.hashmap().values().contains(&3)
-    }
-
-    fn is22(&self) -> bool {
-        self.hashmap().values().filter(|&&v| v == 2).count() == 2
-    }
-
-    fn is2(&self) -> bool {
-        !self.is32() && !self.is22() && self.hashmap().values().contains(&2)
-    }
+    pub fn parse(s: &str, ruleset: &Ruleset) -> Result<Self, ParseHandError> {
+        let (cards, bid) = s.split_once(' ').ok_or(ParseHandError::MissingBid)?;
+        let cards = cards.chars().collect::<Vec<_>>();
+
+        if cards.len() != ruleset.hand_size {
+            return Err(ParseHandError::InvalidLength {
+                expected: ruleset.hand_size,
+                found: cards.len(),
+            });
+        }
 
-    fn compare_cards(&self, other: &Self) -> Ordering {
-        self.cards
+        let values = cards
             .iter()
-            .zip(other.cards.iter())
-            .map(|(a, b)| a.joker_value().cmp(&b.joker_value()))
-            .find(|&o| o != Ordering::Equal)
-            .unwrap()
+            .enumerate()
+            .map(|(position, &card)| {
+                ruleset
+                    .value(card)
+                    .ok_or(ParseHandError::InvalidCard { card, position })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Hand {
+            hand_type: HandType::classify(&cards, ruleset),
+            cards,
+            values,
+            bid: bid.parse().map_err(ParseHandError::InvalidBid)?,
+        })
     }
 
-    fn replace_joker(&mut self) {
-        let hm = self
-            .hashmap()
-            .into_iter()
-            .map(|(k, v)| (k.0, v))
-            .collect::<HashMap<_, _>>();
-
-        for card in self.cards.iter_mut() {
-            if card.0 == 'J' {
-                let found = hm
-                    .iter()
-                    .filter(|(&card, _)| card != 'J')
-                    .max_by_key(|(_, &count)| count);
-
-                if let Some((c, _)) = found {
-                    card.0 = *c;
-                } else {
-                    card.0 = 'A';
-                }
-
-                card.1 = Some(1);
-            }
-        }
+    pub fn hand_type(&self) -> HandType {
+        self.hand_type
     }
 }
 
 impl FromStr for Hand {
-    type Err = ParseIntError;
+    type Err = ParseHandError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (cards, bid) = s.split_once(' ').unwrap();
-        let cards: [Card; 5] = cards
-            .chars()
-            .take(5)
-            .map(Card::new)
-            .collect::<Vec<_>>()
-            .try_into()
-            .unwrap();
+        Self::parse(s, &Ruleset::default())
+    }
+}
 
-        Ok(Hand {
-            cards,
-            bid: bid.parse()?,
-        })
+impl fmt::Display for Hand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.cards.iter().collect::<String>(), self.bid)
     }
 }
 
@@ -166,87 +168,60 @@ impl PartialOrd for Hand {
 
 impl Ord for Hand {
     fn cmp(&self, other: &Self) -> Ordering {
-        if self.is5() && !other.is5() {
-            return Ordering::Greater;
-        }
-
-        if other.is5() && !self.is5() {
-            return Ordering::Less;
-        }
-
-        if self.is4() && !other.is4() {
-            return Ordering::Greater;
-        }
-
-        if other.is4() && !self.is4() {
-            return Ordering::Less;
-        }
-
-        if self.is32() && !other.is32() {
-            return Ordering::Greater;
-        }
-
-        if other.is32() && !self.is32() {
-            return Ordering::Less;
-        }
-
-        if self.is3() && !other.is3() {
-            return Ordering::Greater;
-        }
-
-        if other.is3() && !self.is3() {
-            return Ordering::Less;
-        }
-
-        if self.is22() && !other.is22() {
-            return Ordering::Greater;
-        }
-
-        if other.is22() && !self.is22() {
-            return Ordering::Less;
-        }
+        (self.hand_type, &self.values).cmp(&(other.hand_type, &other.values))
+    }
+}
 
-        if self.is2() && !other.is2() {
-            return Ordering::Greater;
-        }
+pub fn histogram<'a>(hands: impl IntoIterator<Item = &'a Hand>) -> BTreeMap<HandType, usize> {
+    hands
+        .into_iter()
+        .counts_by(Hand::hand_type)
+        .into_iter()
+        .collect()
+}
 
-        if other.is2() && !self.is2() {
-            return Ordering::Less;
+/// Usage: `day7 [--histogram]`
+fn main() {
+    if env::args().any(|arg| arg == "--histogram") {
+        for (name, ruleset) in [
+            ("standard", Ruleset::default()),
+            ("jokers", Ruleset::jokers()),
+        ] {
+            let hands = INPUT
+                .lines()
+                .map(|l| Hand::parse(l, &ruleset).unwrap())
+                .collect::<Vec<_>>();
+
+            println!("{name}:");
+            for (hand_type, count) in histogram(&hands) {
+                println!("  {hand_type:?}: {count}");
+            }
         }
 
-        self.compare_cards(other)
+        return;
     }
-}
 
-fn main() {
     println!("Part 1: {}", part1(INPUT));
     println!("Part 2: {}", part2(INPUT));
 }
 
-fn part1(input: impl AsRef<str>) -> u64 {
+fn winnings(input: impl AsRef<str>, ruleset: &Ruleset) -> u64 {
     input
         .as_ref()
         .lines()
-        .map(|l| l.parse::<Hand>().unwrap())
+        .map(|l| Hand::parse(l, ruleset).unwrap())
         .sorted()
         .enumerate()
         .map(|(i, h)| (i + 1) as u64 * h.bid)
         .sum()
 }
 
+fn part1(input: impl AsRef<str>) -> u64 {
+    winnings(input, &Ruleset::default())
+}
+
 fn part2(input: impl AsRef<str>) -> u64 {
-    input
-        .as_ref()
-        .lines()
-        .map(|l| {
-            let mut hand = l.parse::<Hand>().unwrap();
-            hand.replace_joker();
-            hand
-        })
-        .sorted()
-        .enumerate()
-        .map(|(i, h)| (i + 1) as u64 * h.bid)
-        .sum()
+    winnings(input, &Ruleset::jokers())
 }
 
 #[cfg(test)]
@@ -268,4 +243,110 @@ QQQJA 483";
     fn test_part2() {
         assert_eq!(part2(INPUT), 5905);
     }
+
+    #[test]
+    fn test_hand_type() {
+        let hand_type = |s: &str| s.parse::<Hand>().unwrap().hand_type();
+
+        assert_eq!(hand_type("AAAAA 1"), HandType::FiveOfAKind);
+        assert_eq!(hand_type("AA8AA 1"), HandType::FourOfAKind);
+        assert_eq!(hand_type("23332 1"), HandType::FullHouse);
+        assert_eq!(hand_type("TTT98 1"), HandType::ThreeOfAKind);
+        assert_eq!(hand_type("23432 1"), HandType::TwoPair);
+        assert_eq!(hand_type("A23A4 1"), HandType::OnePair);
+        assert_eq!(hand_type("23456 1"), HandType::HighCard);
+    }
+
+    #[test]
+    fn test_histogram() {
+        let hands = INPUT
+            .lines()
+            .map(|l| l.parse::<Hand>().unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            histogram(&hands),
+            BTreeMap::from([
+                (HandType::OnePair, 1),
+                (HandType::TwoPair, 2),
+                (HandType::ThreeOfAKind, 2),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_rulesets() {
+        let hand_type = |s: &str, ruleset: &Ruleset| Hand::parse(s, ruleset).unwrap().hand_type();
+
+        assert_eq!(
+            hand_type("JJJJJ 1", &Ruleset::jokers()),
+            HandType::FiveOfAKind
+        );
+        assert_eq!(
+            hand_type("KTJJT 1", &Ruleset::jokers()),
+            HandType::FourOfAKind
+        );
+        assert_eq!(hand_type("2345J 1", &Ruleset::jokers()), HandType::OnePair);
+
+        let two_wild = Ruleset::new("JQ23456789TKA", "JQ");
+        assert_eq!(hand_type("2QJ34 1", &two_wild), HandType::ThreeOfAKind);
+        assert_eq!(winnings(INPUT, &two_wild), 6168);
+
+        let aces_low = Ruleset::new("A23456789TJQK", "");
+        assert!(
+            Hand::parse("A2345 1", &aces_low).unwrap() < Hand::parse("23456 1", &aces_low).unwrap()
+        );
+        assert_eq!(winnings(INPUT, &aces_low), 6440);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            "32T3 765".parse::<Hand>(),
+            Err(ParseHandError::InvalidLength {
+                expected: 5,
+                found: 4
+            })
+        );
+        assert_eq!(
+            "32T3KK 765".parse::<Hand>(),
+            Err(ParseHandError::InvalidLength {
+                expected: 5,
+                found: 6
+            })
+        );
+        assert_eq!(
+            "32X3K 765".parse::<Hand>(),
+            Err(ParseHandError::InvalidCard {
+                card: 'X',
+                position: 2
+            })
+        );
+        assert_eq!("32T3K".parse::<Hand>(), Err(ParseHandError::MissingBid));
+        assert!(matches!(
+            "32T3K abc".parse::<Hand>(),
+            Err(ParseHandError::InvalidBid(_))
+        ));
+    }
+
+    #[test]
+    fn test_hand_size() {
+        let ruleset = Ruleset::default().with_hand_size(3);
+        let hand = Hand::parse("KKA 10", &ruleset).unwrap();
+
+        assert_eq!(hand.hand_type(), HandType::OnePair);
+        assert!(Hand::parse("KKAA 10", &ruleset).is_err());
+
+        let ruleset = Ruleset::default().with_hand_size(7);
+        let hand = Hand::parse("2233344 10", &ruleset).unwrap();
+
+        assert_eq!(hand.hand_type(), HandType::FullHouse);
+    }
+
+    #[test]
+    fn test_display() {
+        for line in INPUT.lines() {
+            assert_eq!(line.parse::<Hand>().unwrap().to_string(), line);
+        }
+    }
 }
@@ -1,73 +1,393 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
-use num::integer::lcm;
+use derive_more::Display;
+use itertools::Itertools;
+use num::integer::Integer;
 
 const INPUT: &str = include_str!("../input/day8.txt");
 
-fn main() {
-    println!("Part 1: {}", part1(INPUT));
-    println!("Part 2: {}", part2(INPUT));
+#[derive(Debug, PartialEq)]
+struct Orbit {
+    cycle_start: usize,
+    cycle_len: usize,
+    prefix_hits: Vec<usize>,
+    cycle_hits: Vec<usize>,
 }
 
-fn part1(input: &str) -> usize {
-    let (instructions, rules) = parse(input);
-    calc_steps("AAA", &instructions, &rules, |c| c != "ZZZ")
-}
+impl Orbit {
+    fn is_hit(&self, step: usize) -> bool {
+        if step < self.cycle_start {
+            return self.prefix_hits.binary_search(&step).is_ok();
+        }
 
-fn part2(input: &str) -> usize {
-    let (instructions, rules) = parse(input);
+        let offset = (step - self.cycle_start) % self.cycle_len;
+        self.cycle_hits
+            .iter()
+            .any(|&hit| (hit - self.cycle_start) % self.cycle_len == offset)
+    }
+
+    fn residues(&self) -> impl Iterator<Item = (i128, i128)> + '_ {
+        self.cycle_hits
+            .iter()
+            .map(|&hit| ((hit % self.cycle_len) as i128, self.cycle_len as i128))
+    }
+}
 
-    let mut result = rules
-        .keys()
-        .filter(|&k| k.ends_with('A'))
-        .map(|&k| calc_steps(k, &instructions, &rules, |c| !c.ends_with('Z')));
+#[derive(Debug, Display, PartialEq)]
+enum NetworkError {
+    #[display("node {_0} is referenced but never defined")]
+    UndefinedNode(String),
+    #[display("unknown start node {_0}")]
+    UnknownNode(String),
+    #[display("no target is reachable from {_0}")]
+    Unreachable(String),
+    #[display("starts never reach targets on the same step")]
+    NoCommonStep,
+    #[display("combined cycle period is too large to search")]
+    SearchTooLarge,
+}
 
-    vec_lcm(&mut result)
+struct Network<'a> {
+    instructions: Vec<usize>,
+    names: Vec<&'a str>,
+    ids: HashMap<&'a str, u32>,
+    edges: Vec<[u32; 2]>,
+    passes: Vec<u32>,
 }
 
-fn calc_steps<F>(
-    current: &str,
-    instructions: &[char],
-    rules: &HashMap<&str, [&str; 2]>,
-    f: F,
-) -> usize
-where
-    F: Fn(&str) -> bool,
-{
-    (0..)
-        .zip(instructions.iter().cycle())
-        .scan(current, |curr, (_, c)| {
-            *curr = rules.get(curr).unwrap()[(c == &'R') as usize];
-            f(curr).then_some(*curr)
+impl<'a> Network<'a> {
+    fn parse(input: &'a str) -> Result<Self, NetworkError> {
+        let mut lines = input.lines();
+
+        let instructions = lines
+            .next()
+            .unwrap()
+            .chars()
+            .map(|c| (c == 'R') as usize)
+            .collect::<Vec<_>>();
+        lines.next();
+
+        let rules = lines
+            .map(|line| {
+                let (key, parts) = line.split_once(" = ").unwrap();
+                let (left, right) = parts
+                    .trim_matches(|c| c == '(' || c == ')')
+                    .split_once(", ")
+                    .unwrap();
+
+                (key, [left, right])
+            })
+            .collect::<Vec<_>>();
+
+        let names = rules.iter().map(|&(key, _)| key).collect::<Vec<_>>();
+        let ids = (0..)
+            .zip(&names)
+            .map(|(id, &name)| (name, id))
+            .collect::<HashMap<_, _>>();
+        let edges = rules
+            .iter()
+            .map(|(_, [left, right])| {
+                let id = |name: &str| {
+                    ids.get(name)
+                        .copied()
+                        .ok_or_else(|| NetworkError::UndefinedNode(name.to_string()))
+                };
+
+                Ok([id(left)?, id(right)?])
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let passes = (0..edges.len() as u32)
+            .map(|node| {
+                instructions
+                    .iter()
+                    .fold(node, |node, &instruction| edges[node as usize][instruction])
+            })
+            .collect();
+
+        Ok(Self {
+            instructions,
+            names,
+            ids,
+            edges,
+            passes,
         })
-        .count()
-        + 1
+    }
+
+    fn id(&self, name: &str) -> Result<u32, NetworkError> {
+        self.ids
+            .get(name)
+            .copied()
+            .ok_or_else(|| NetworkError::UnknownNode(name.to_string()))
+    }
+
+    fn reachable(&self, start: u32) -> Vec<bool> {
+        let mut directions = self.instructions.clone();
+        directions.sort_unstable();
+        directions.dedup();
+
+        let mut reachable = vec![false; self.edges.len()];
+        let mut queue = VecDeque::from([start]);
+        reachable[start as usize] = true;
+
+        while let Some(node) = queue.pop_front() {
+            for &direction in &directions {
+                let next = self.edges[node as usize][direction];
+                if !reachable[next as usize] {
+                    reachable[next as usize] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        reachable
+    }
+
+    #[allow(dead_code)]
+    fn walk(&self, node: u32, steps: usize) -> u32 {
+        let len = self.instructions.len();
+        let node = (0..steps / len).fold(node, |node, _| self.passes[node as usize]);
+
+        self.instructions[..steps % len]
+            .iter()
+            .fold(node, |node, &instruction| {
+                self.edges[node as usize][instruction]
+            })
+    }
+
+    fn pass_hits(&self, is_end: impl Fn(&str) -> bool) -> Vec<Vec<usize>> {
+        let ends = self
+            .names
+            .iter()
+            .map(|name| is_end(name))
+            .collect::<Vec<_>>();
+
+        (0..self.edges.len() as u32)
+            .map(|start| {
+                self.instructions
+                    .iter()
+                    .scan(start, |node, &instruction| {
+                        let current = *node;
+                        *node = self.edges[current as usize][instruction];
+                        Some(current)
+                    })
+                    .positions(|node| ends[node as usize])
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn orbit(&self, start: u32, pass_hits: &[Vec<usize>]) -> Orbit {
+        let len = self.instructions.len();
+        let mut seen = HashMap::new();
+        let mut hits = Vec::new();
+        let mut node = start;
+
+        for pass in 0.. {
+            if let Some(&cycle_start) = seen.get(&node) {
+                let cycle_start = cycle_start * len;
+                let cycle_len = pass * len - cycle_start;
+                let (prefix_hits, cycle_hits): (Vec<_>, Vec<_>) =
+                    hits.iter().partition(|&&hit| hit < cycle_start);
+
+                // Step 0 is never an answer, but when the cycle starts there it
+                // recurs one cycle later.
+                return Orbit {
+                    cycle_start,
+                    cycle_len,
+                    prefix_hits: prefix_hits.into_iter().filter(|&hit| hit > 0).collect(),
+                    cycle_hits: cycle_hits
+                        .into_iter()
+                        .map(|hit| if hit == 0 { cycle_len } else { hit })
+                        .sorted()
+                        .collect(),
+                };
+            }
+
+            seen.insert(node, pass);
+            hits.extend(
+                pass_hits[node as usize]
+                    .iter()
+                    .map(|offset| pass * len + offset),
+            );
+
+            node = self.passes[node as usize];
+        }
+
+        unreachable!()
+    }
+
+    fn steps<'b>(
+        &self,
+        starts: impl IntoIterator<Item = &'b str>,
+        is_end: impl Fn(&str) -> bool,
+    ) -> Result<usize, NetworkError> {
+        let pass_hits = self.pass_hits(&is_end);
+        let orbits = starts
+            .into_iter()
+            .map(|start| {
+                let id = self.id(start)?;
+                let reachable = self.reachable(id);
+                let unreachable = || NetworkError::Unreachable(start.to_string());
+
+                if !(0..self.names.len()).any(|node| reachable[node] && is_end(self.names[node])) {
+                    return Err(unreachable());
+                }
+
+                let orbit = self.orbit(id, &pass_hits);
+                if orbit.prefix_hits.is_empty() && orbit.cycle_hits.is_empty() {
+                    return Err(unreachable());
+                }
+
+                Ok(orbit)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let settled = orbits
+            .iter()
+            .map(|orbit| orbit.cycle_start)
+            .max()
+            .ok_or(NetworkError::NoCommonStep)?
+            .max(1);
+        let early = (1..settled).find(|&step| orbits.iter().all(|orbit| orbit.is_hit(step)));
+
+        if let Some(step) = early {
+            return Ok(step);
+        }
+
+        match combine(&orbits, settled) {
+            Combination::Found(step) => Ok(step),
+            Combination::Impossible => Err(NetworkError::NoCommonStep),
+            Combination::TooMany => simulate(&orbits, settled),
+        }
+    }
+
+    #[allow(dead_code)]
+    fn path(
+        &self,
+        start: &str,
+        is_end: impl Fn(&str) -> bool,
+    ) -> Result<Vec<&'a str>, NetworkError> {
+        let steps = self.steps([start], is_end)?;
+
+        Ok(self
+            .instructions
+            .iter()
+            .cycle()
+            .take(steps)
+            .scan(self.id(start)?, |node, &instruction| {
+                *node = self.edges[*node as usize][instruction];
+                Some(*node)
+            })
+            .map(|node| self.names[node as usize])
+            .collect())
+    }
 }
 
-fn vec_lcm(iter: &mut impl Iterator<Item = usize>) -> usize {
-    let first = iter.next().unwrap();
-    iter.fold(first, lcm)
+const MAX_COMBINATIONS: usize = 1 << 12;
+const MAX_SIMULATED_CYCLES: usize = 1 << 24;
+
+#[derive(Debug, PartialEq)]
+enum Combination {
+    Found(usize),
+    /// Every system of congruences was checked and none has a solution.
+    Impossible,
+    /// Too many systems to check, or the answer does not fit a `usize`.
+    TooMany,
+}
+
+fn crt((a1, m1): (i128, i128), (a2, m2): (i128, i128)) -> Option<(i128, i128)> {
+    let gcd = m1.extended_gcd(&m2);
+    if (a2 - a1) % gcd.gcd != 0 {
+        return None;
+    }
+
+    let modulus = m1 / gcd.gcd * m2;
+    let k = ((a2 - a1) / gcd.gcd * gcd.x).mod_floor(&(m2 / gcd.gcd));
+
+    Some(((a1 + m1 * k).mod_floor(&modulus), modulus))
 }
 
-fn parse(input: &str) -> (Vec<char>, HashMap<&str, [&str; 2]>) {
-    let mut lines = input.lines();
+fn combine(orbits: &[Orbit], settled: usize) -> Combination {
+    let combinations = orbits
+        .iter()
+        .try_fold(1usize, |acc, orbit| acc.checked_mul(orbit.cycle_hits.len()));
 
-    let instructions = lines.next().unwrap().chars().collect::<Vec<_>>();
-    lines.next();
+    if combinations.is_none_or(|combinations| combinations > MAX_COMBINATIONS) {
+        return Combination::TooMany;
+    }
+
+    let mut systems = vec![(0, 1)];
+    for orbit in orbits {
+        systems = systems
+            .iter()
+            .flat_map(|&system| orbit.residues().filter_map(move |r| crt(system, r)))
+            .collect();
+    }
+
+    let settled = settled as i128;
+    systems
+        .into_iter()
+        .map(|(residue, modulus)| {
+            residue + ((settled - residue).max(0) + modulus - 1) / modulus * modulus
+        })
+        .min()
+        .map_or(Combination::Impossible, |step| {
+            usize::try_from(step).map_or(Combination::TooMany, Combination::Found)
+        })
+}
 
-    let rules = lines
-        .map(|line| {
-            let (key, parts) = line.split_once(" = ").unwrap();
-            let (left, right) = parts
-                .trim_matches(|c| c == '(' || c == ')')
-                .split_once(", ")
-                .unwrap();
+fn simulate(orbits: &[Orbit], settled: usize) -> Result<usize, NetworkError> {
+    let pivot = orbits
+        .iter()
+        .max_by_key(|orbit| orbit.cycle_len)
+        .ok_or(NetworkError::NoCommonStep)?;
+    let period = orbits
+        .iter()
+        .try_fold(1usize, |acc, orbit| {
+            (acc / acc.gcd(&orbit.cycle_len)).checked_mul(orbit.cycle_len)
+        })
+        .filter(|&period| period / pivot.cycle_len <= MAX_SIMULATED_CYCLES)
+        .ok_or(NetworkError::SearchTooLarge)?;
+    let base = settled - (settled - pivot.cycle_start) % pivot.cycle_len;
+    let end = settled
+        .checked_add(period)
+        .and_then(|end| end.checked_add(pivot.cycle_len))
+        .ok_or(NetworkError::SearchTooLarge)?;
 
-            (key, [left, right])
+    (base..=end - pivot.cycle_len)
+        .step_by(pivot.cycle_len)
+        .flat_map(|step| {
+            pivot
+                .cycle_hits
+                .iter()
+                .map(move |&hit| step + hit - pivot.cycle_start)
         })
-        .collect();
+        .filter(|&step| step >= settled && orbits.iter().all(|orbit| orbit.is_hit(step)))
+        .min()
+        .ok_or(NetworkError::NoCommonStep)
+}
+
+fn main() {
+    println!("Part 1: {}", part1(INPUT));
+    println!("Part 2: {}", part2(INPUT));
+}
+
+fn part1(input: &str) -> usize {
+    let network = Network::parse(input).unwrap();
+    network.steps(["AAA"], |node| node == "ZZZ").unwrap()
+}
+
+fn part2(input: &str) -> usize {
+    let network = Network::parse(input).unwrap();
+    let starts = network
+        .names
+        .iter()
+        .copied()
+        .filter(|node| node.ends_with('A'))
+        .collect::<Vec<_>>();
 
-    (instructions, rules)
+    network.steps(starts, |node| node.ends_with('Z')).unwrap()
 }
 
 #[cfg(test)]
@@ -106,4 +426,186 @@ XXX = (XXX, XXX)"
             6
         );
     }
+
+    #[test]
+    fn test_orbit() {
+        let network = Network::parse(
+            "LR
+
+11A = (11B, XXX)
+11B = (XXX, 11Z)
+11Z = (11B, XXX)
+XXX = (XXX, XXX)",
+        )
+        .unwrap();
+
+        let pass_hits = network.pass_hits(|node| node.ends_with('Z'));
+
+        assert_eq!(
+            network.orbit(network.id("11A").unwrap(), &pass_hits),
+            Orbit {
+                cycle_start: 2,
+                cycle_len: 2,
+                prefix_hits: vec![],
+                cycle_hits: vec![2],
+            }
+        );
+    }
+
+    #[test]
+    fn test_unaligned_cycles() {
+        // 11A reaches 11Z after 3 steps and every 2 steps after that, 22A
+        // reaches 22Z after 2 steps and then every 3 steps, so plain LCM of
+        // first arrivals (6) is wrong.
+        let network = Network::parse(
+            "L
+
+11A = (11B, 11B)
+11B = (11C, 11C)
+11C = (11Z, 11Z)
+11Z = (11D, 11D)
+11D = (11Z, 11Z)
+22A = (22B, 22B)
+22B = (22Z, 22Z)
+22Z = (22C, 22C)
+22C = (22D, 22D)
+22D = (22Z, 22Z)",
+        )
+        .unwrap();
+
+        let starts = ["11A", "22A"];
+        assert_eq!(network.steps(starts, |node| node.ends_with('Z')), Ok(5));
+    }
+
+    #[test]
+    fn test_multiple_hits() {
+        let network = Network::parse(
+            "L
+
+11A = (11Z, 11Z)
+11Z = (11B, 11B)
+11B = (12Z, 12Z)
+12Z = (11C, 11C)
+11C = (11A, 11A)
+22A = (22B, 22B)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22A, 22A)",
+        )
+        .unwrap();
+
+        let starts = ["11A", "22A"];
+        assert_eq!(network.steps(starts, |node| node.ends_with('Z')), Ok(3));
+        assert_eq!(
+            network.steps(starts, |node| node == "11Z" || node == "22Z"),
+            Ok(11)
+        );
+        assert_eq!(
+            network.steps(["11A"], |node| node == "22Z"),
+            Err(NetworkError::Unreachable("11A".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_walk() {
+        let network = Network::parse(
+            "LLR
+
+AAA = (BBB, BBB)
+BBB = (AAA, ZZZ)
+ZZZ = (ZZZ, ZZZ)",
+        )
+        .unwrap();
+
+        let start = network.id("AAA").unwrap();
+        assert_eq!(network.walk(start, 0), start);
+        assert_eq!(network.walk(start, 5), network.id("BBB").unwrap());
+        assert_eq!(network.walk(start, 6), network.id("ZZZ").unwrap());
+        assert_eq!(network.walk(start, 5_000_000), network.id("ZZZ").unwrap());
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Network::parse("L\n\nAAA = (BBB, ZZZ)\nZZZ = (ZZZ, ZZZ)").err(),
+            Some(NetworkError::UndefinedNode("BBB".to_string()))
+        );
+
+        // ZZZ is only reachable by going right
+        let network =
+            Network::parse("L\n\nAAA = (BBB, ZZZ)\nBBB = (AAA, AAA)\nZZZ = (ZZZ, ZZZ)").unwrap();
+        assert_eq!(
+            network.steps(["AAA"], |node| node == "ZZZ"),
+            Err(NetworkError::Unreachable("AAA".to_string()))
+        );
+        assert_eq!(
+            network.steps(["QQQ"], |node| node == "ZZZ"),
+            Err(NetworkError::UnknownNode("QQQ".to_string()))
+        );
+
+        // both targets are reachable, but only on odd and even steps respectively
+        let network = Network::parse("L\n\n11A = (11Z, 11Z)\n11Z = (11A, 11A)\n22A = (22B, 22B)\n22B = (22Z, 22Z)\n22Z = (22B, 22B)").unwrap();
+        assert_eq!(
+            network.steps(["11A", "22A"], |node| node.ends_with('Z')),
+            Err(NetworkError::NoCommonStep)
+        );
+    }
+
+    #[test]
+    fn test_combine_and_simulate() {
+        let orbit = |cycle_len, hit| Orbit {
+            cycle_start: 0,
+            cycle_len,
+            prefix_hits: vec![],
+            cycle_hits: vec![hit],
+        };
+
+        let orbits = [orbit(3, 1), orbit(4, 2)];
+        assert_eq!(combine(&orbits, 1), Combination::Found(10));
+        assert_eq!(simulate(&orbits, 1), Ok(10));
+
+        // conflicting parity is proven impossible without simulating
+        let orbits = [orbit(20_000, 1), orbit(20_002, 2)];
+        assert_eq!(combine(&orbits, 1), Combination::Impossible);
+
+        let primes = [19_991, 19_993, 19_997, 20_011, 20_021, 20_023];
+        let orbits = primes.map(|prime| orbit(prime, 1));
+        assert_eq!(simulate(&orbits[..3], 1), Err(NetworkError::SearchTooLarge));
+        assert_eq!(simulate(&orbits, 1), Err(NetworkError::SearchTooLarge));
+    }
+
+    #[test]
+    fn test_start_is_target() {
+        let network = Network::parse("L\n\nAAZ = (BBB, BBB)\nBBB = (AAZ, AAZ)").unwrap();
+        assert_eq!(network.steps(["AAZ"], |node| node.ends_with('Z')), Ok(2));
+
+        let network = Network::parse("L\n\nZZZ = (ZZZ, ZZZ)").unwrap();
+        assert_eq!(network.steps(["ZZZ"], |node| node == "ZZZ"), Ok(1));
+
+        let network = Network::parse(
+            "LR\n\nAAZ = (BBB, CCC)\nBBB = (AAZ, AAZ)\nCCC = (AAZ, AAZ)\nZZZ = (ZZZ, ZZZ)",
+        )
+        .unwrap();
+        assert_eq!(
+            network.steps(["AAZ", "ZZZ"], |node| node.ends_with('Z')),
+            Ok(2)
+        );
+    }
+
+    #[test]
+    fn test_path() {
+        let network = Network::parse(
+            "LLR
+
+AAA = (BBB, BBB)
+BBB = (AAA, ZZZ)
+ZZZ = (ZZZ, ZZZ)",
+        )
+        .unwrap();
+
+        assert_eq!(
+            network.path("AAA", |node| node == "ZZZ"),
+            Ok(vec!["BBB", "AAA", "BBB", "AAA", "BBB", "ZZZ"])
+        );
+    }
 }
@@ -1,25 +1,106 @@
+use std::{fmt::Debug, str::FromStr};
+
 use aoc::parse_nums;
+use derive_more::Display;
 use itertools::Itertools;
+use num::{rational::Ratio, CheckedAdd, CheckedMul, CheckedSub, FromPrimitive, Integer, One, Zero};
 
 const INPUT: &str = include_str!("../input/day9.txt");
 
-struct Report(Vec<i64>);
+#[derive(Debug, Display, PartialEq)]
+enum ReportError {
+    #[display("differences never reach zero, sequence is not a polynomial")]
+    NotPolynomial,
+    #[display("value does not fit the integer type")]
+    Overflow,
+}
+
+struct Report<T> {
+    len: usize,
+    coefficients: Vec<T>,
+}
+
+impl<T> FromStr for Report<T>
+where
+    T: Integer + Clone + CheckedSub + CheckedAdd + CheckedMul + FromPrimitive + FromStr,
+    <T as FromStr>::Err: Debug,
+{
+    type Err = ReportError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(parse_nums(s))
+    }
+}
+
+impl<T> Report<T>
+where
+    T: Integer + Clone + CheckedSub + CheckedAdd + CheckedMul + FromPrimitive,
+{
+    fn new(values: Vec<T>) -> Result<Self, ReportError> {
+        let len = values.len();
+        let mut current = values;
+        let mut coefficients = vec![];
+
+        while !current.iter().all(Zero::is_zero) {
+            coefficients.push(current[0].clone());
+            current = current
+                .iter()
+                .tuple_windows()
+                .map(|(a, b)| b.checked_sub(a).ok_or(ReportError::Overflow))
+                .collect::<Result<_, _>>()?;
+        }
+
+        if current.is_empty() {
+            return Err(ReportError::NotPolynomial);
+        }
+
+        Ok(Self { len, coefficients })
+    }
 
-impl Report {
-    fn new(input: impl AsRef<str>) -> Self {
-        Self(parse_nums(input))
+    #[allow(dead_code)]
+    fn degree(&self) -> usize {
+        self.coefficients.len().saturating_sub(1)
     }
 
-    fn extrapolate(&self, f: &impl Fn(&[(i64, i64)]) -> i64) -> i64 {
-        let mut items = vec![];
-        let mut current = self.0.clone();
+    fn evaluate(&self, x: T) -> Result<T, ReportError> {
+        let mut sum = Ratio::<T>::zero();
+        let mut binomial = Ratio::<T>::one();
+
+        for (k, coefficient) in self.coefficients.iter().enumerate() {
+            let term = binomial.checked_mul(&Ratio::from(coefficient.clone()));
+            sum = term
+                .and_then(|term| sum.checked_add(&term))
+                .ok_or(ReportError::Overflow)?;
 
-        while !current.iter().all(|v| *v == 0) {
-            items.push((*current.first().unwrap(), *current.last().unwrap()));
-            current = current.iter().tuple_windows().map(|(a, b)| b - a).collect();
+            // once `x - k` hits zero every later binomial, and so every term, is zero
+            let last = k + 1 == self.coefficients.len();
+            let k = T::from_usize(k).ok_or(ReportError::Overflow)?;
+            if last || x == k {
+                break;
+            }
+
+            let factor = x
+                .checked_sub(&k)
+                .zip(k.checked_add(&T::one()))
+                .map(|(numer, denom)| Ratio::new(numer, denom));
+            binomial = factor
+                .and_then(|factor| binomial.checked_mul(&factor))
+                .ok_or(ReportError::Overflow)?;
         }
 
-        f(&items)
+        Ok(sum.to_integer())
+    }
+
+    fn next(&self) -> Result<T, ReportError> {
+        self.evaluate(T::from_usize(self.len).ok_or(ReportError::Overflow)?)
+    }
+
+    fn previous(&self) -> Result<T, ReportError> {
+        self.evaluate(
+            T::zero()
+                .checked_sub(&T::one())
+                .ok_or(ReportError::Overflow)?,
+        )
     }
 }
 
@@ -29,30 +110,25 @@ fn main() {
 }
 
 fn part1(input: impl AsRef<str>) -> i64 {
-    solve(input, |items| items.iter().map(|item| item.1).sum())
+    solve(input, Report::next)
 }
 
 fn part2(input: impl AsRef<str>) -> i64 {
-    solve(input, |items| {
-        items
-            .iter()
-            .map(|item| item.0)
-            .rev()
-            .fold(0, |acc, value| value - acc)
-    })
+    solve(input, Report::previous)
 }
 
-fn solve(input: impl AsRef<str>, f: impl Fn(&[(i64, i64)]) -> i64) -> i64 {
+fn solve(input: impl AsRef<str>, f: impl Fn(&Report<i64>) -> Result<i64, ReportError>) -> i64 {
     input
         .as_ref()
         .lines()
-        .map(Report::new)
-        .map(|r| r.extrapolate(&f))
+        .map(|line| f(&line.parse().unwrap()).unwrap())
         .sum()
 }
 
 #[cfg(test)]
 mod tests {
+    use num::BigInt;
+
     use super::*;
 
     #[test]
@@ -67,4 +143,82 @@ mod tests {
     fn test_part2() {
         assert_eq!(part2("10  13  16  21  30  45"), 5);
     }
+
+    #[test]
+    fn test_evaluate() {
+        let report = "1 3 6 10 15 21".parse::<Report<i64>>().unwrap();
+
+        assert_eq!(report.degree(), 2);
+        assert_eq!(report.evaluate(0), Ok(1));
+        assert_eq!(report.evaluate(6), Ok(28));
+        assert_eq!(report.evaluate(-1), Ok(0));
+        assert_eq!(report.evaluate(-5), Ok(6));
+        assert_eq!(report.evaluate(1_000_000), Ok(500_001_500_001));
+    }
+
+    #[test]
+    fn test_degree() {
+        assert_eq!("7 7 7 7".parse::<Report<i64>>().unwrap().degree(), 0);
+        assert_eq!("0 3 6 9 12 15".parse::<Report<i64>>().unwrap().degree(), 1);
+        assert_eq!("0 1 8 27 64".parse::<Report<i64>>().unwrap().degree(), 3);
+        assert_eq!(
+            "0 1 8 27 64".parse::<Report<i64>>().unwrap().evaluate(-3),
+            Ok(-27)
+        );
+    }
+
+    #[test]
+    fn test_not_polynomial() {
+        assert_eq!(
+            "1 2 4 8 16".parse::<Report<i64>>().err(),
+            Some(ReportError::NotPolynomial)
+        );
+        assert_eq!(
+            "1 2".parse::<Report<i64>>().err(),
+            Some(ReportError::NotPolynomial)
+        );
+        assert_eq!(
+            "".parse::<Report<i64>>().err(),
+            Some(ReportError::NotPolynomial)
+        );
+        assert!("0 0".parse::<Report<i64>>().is_ok());
+    }
+
+    #[test]
+    fn test_overflow() {
+        let input = "0 4000000000000000000 8000000000000000000";
+
+        assert_eq!(
+            input.parse::<Report<i64>>().unwrap().next(),
+            Err(ReportError::Overflow)
+        );
+        assert_eq!(
+            input.parse::<Report<BigInt>>().unwrap().next(),
+            Ok("12000000000000000000".parse().unwrap())
+        );
+        // the last coefficient never needs the next binomial
+        let report = Report::new(vec![i64::MAX - 2, i64::MAX - 1, i64::MAX]).unwrap();
+        assert_eq!(report.evaluate(i64::MIN), Ok(-3));
+        assert_eq!(
+            "-9000000000000000000 9000000000000000000 0"
+                .parse::<Report<i64>>()
+                .err(),
+            Some(ReportError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_unsigned() {
+        let report = "1 3 6 10 15 21".parse::<Report<u64>>().unwrap();
+
+        assert_eq!(report.evaluate(0), Ok(1));
+        assert_eq!(report.evaluate(1), Ok(3));
+        assert_eq!(report.evaluate(5), Ok(21));
+        assert_eq!(report.next(), Ok(28));
+        assert_eq!(report.previous(), Err(ReportError::Overflow));
+        assert_eq!(
+            "3 2 1".parse::<Report<u64>>().err(),
+            Some(ReportError::Overflow)
+        );
+    }
 }
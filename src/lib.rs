@@ -85,9 +85,9 @@ pub trait MatrixExt {
 
     fn column(&self, column: usize) -> Vec<&Self::Item>;
 
-    fn columns_iter(&self) -> MatrixIter<Self::Item, Self::Iter, Vec<&Self::Item>>;
+    fn columns_iter(&self) -> MatrixIter<'_, Self::Item, Self::Iter, Vec<&Self::Item>>;
 
-    fn rows_iter(&self) -> MatrixIter<Self::Item, Self::Iter, Vec<&Self::Item>>;
+    fn rows_iter(&self) -> MatrixIter<'_, Self::Item, Self::Iter, Vec<&Self::Item>>;
 }
 
 impl<T> MatrixExt for Matrix<T> {
@@ -104,7 +104,7 @@ impl<T> MatrixExt for Matrix<T> {
         (0..self.rows).map(|row| &self[(row, column)]).collect()
     }
 
-    fn columns_iter(&self) -> MatrixIter<Self::Item, Self::Iter, Vec<&Self::Item>> {
+    fn columns_iter(&self) -> MatrixIter<'_, Self::Item, Self::Iter, Vec<&Self::Item>> {
         MatrixIter::new(self, |matrix, index| {
             if index < matrix.columns {
                 Some((0..matrix.rows).map(|row| &matrix[(row, index)]).collect())
@@ -114,7 +114,7 @@ impl<T> MatrixExt for Matrix<T> {
         })
     }
 
-    fn rows_iter(&self) -> MatrixIter<Self::Item, Self::Iter, Vec<&Self::Item>> {
+    fn rows_iter(&self) -> MatrixIter<'_, Self::Item, Self::Iter, Vec<&Self::Item>> {
         MatrixIter::new(self, |matrix, index| {
             if index < matrix.rows {
                 Some(
@@ -128,3 +128,39 @@ impl<T> MatrixExt for Matrix<T> {
         })
     }
 }
+
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Ord, PartialOrd)]
+pub enum Direction {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::North,
+        Direction::East,
+        Direction::South,
+        Direction::West,
+    ];
+
+    pub fn opposite(self) -> Self {
+        match self {
+            Direction::North => Direction::South,
+            Direction::East => Direction::West,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
+        }
+    }
+
+    /// Offset as `(row, column)`, suitable for `Matrix::move_in_direction`.
+    pub fn offset(self) -> (isize, isize) {
+        match self {
+            Direction::North => (-1, 0),
+            Direction::East => (0, 1),
+            Direction::South => (1, 0),
+            Direction::West => (0, -1),
+        }
+    }
+}